@@ -0,0 +1,39 @@
+//! Compares many threads adding to one Mutex-backed container against a sharded one. Sharding only pays off
+//! when the threads really run in parallel, so run it on a machine with several cores:
+//! `cargo bench --bench contention`.
+
+use std::time::{Duration, Instant};
+
+use fast_container::{ShardedStableIndexVec, SyncStableIndexVec};
+
+const THREADS: usize = 8;
+const ADDS_PER_THREAD: usize = 100_000;
+
+fn time_adds(add: impl Fn(usize) + Sync) -> Duration {
+    let start = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for i in 0..ADDS_PER_THREAD {
+                    add(i);
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let single = SyncStableIndexVec::new();
+    let sharded = ShardedStableIndexVec::with_shards(THREADS);
+
+    let single_time = time_adds(|i| {
+        single.add(i);
+    });
+    let sharded_time = time_adds(|i| {
+        sharded.add(i);
+    });
+
+    println!("{THREADS} threads x {ADDS_PER_THREAD} adds, one mutex: {single_time:?}");
+    println!("{THREADS} threads x {ADDS_PER_THREAD} adds, {THREADS} shards:  {sharded_time:?}");
+}
@@ -0,0 +1,44 @@
+//! Compares iterating a dense container against one that had 90% of its elements removed.
+//! There's no "before" number to compare with: removal already swap-pops, so elements stay contiguous and no
+//! occupied-slot bitset was added. The baseline is instead a fresh container holding the same number of elements.
+//! Run with `cargo bench --bench iteration`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use fast_container::StableIndexVec;
+
+const ELEMENTS: usize = 1_000_000;
+const ROUNDS: u32 = 20;
+
+fn time_iteration(container: &StableIndexVec<u64>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let sum: u64 = container.values().copied().sum();
+        black_box(sum);
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let mut container = StableIndexVec::with_capacity(ELEMENTS);
+    let keys: Vec<_> = (0..ELEMENTS as u64).map(|i| container.add(i)).collect();
+    let full = time_iteration(&container);
+
+    for (i, key) in keys.into_iter().enumerate() {
+        if i % 10 != 0 {
+            container.remove(key);
+        }
+    }
+    let sparse = time_iteration(&container);
+
+    let mut dense = StableIndexVec::with_capacity(container.len());
+    for value in container.values() {
+        dense.add(*value);
+    }
+    let dense_same_len = time_iteration(&dense);
+
+    println!("iterate {ELEMENTS} elements:                     {full:?}");
+    println!("iterate after removing 90% ({} left):    {sparse:?}", container.len());
+    println!("iterate a fresh container of {} elements: {dense_same_len:?}", dense.len());
+}
@@ -0,0 +1,34 @@
+//! Compares finding a key by value with the linear scan in StableIndexVec against the hashed index in
+//! IndexedStableIndexVec. Run with `cargo bench --bench lookup --features value-index`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use fast_container::{IndexedStableIndexVec, StableIndexVec};
+
+const ELEMENTS: u64 = 100_000;
+const LOOKUPS: u64 = 1_000;
+
+fn time_lookups(mut key_of: impl FnMut(u64) -> bool) -> Duration {
+    let start = Instant::now();
+    for i in 0..LOOKUPS {
+        // spread the lookups over the whole container, so the scan averages half its length
+        black_box(key_of(i * (ELEMENTS / LOOKUPS)));
+    }
+    start.elapsed() / LOOKUPS as u32
+}
+
+fn main() {
+    let mut plain = StableIndexVec::with_capacity(ELEMENTS as usize);
+    let mut indexed = IndexedStableIndexVec::new();
+    for i in 0..ELEMENTS {
+        plain.add(i);
+        indexed.add(i);
+    }
+
+    let scan = time_lookups(|value| plain.key_of(&value).is_some());
+    let hashed = time_lookups(|value| indexed.key_of(&value).is_some());
+
+    println!("key_of over {ELEMENTS} elements, linear scan: {scan:?}");
+    println!("key_of over {ELEMENTS} elements, value index: {hashed:?}");
+}
@@ -0,0 +1,32 @@
+//! Shows the difference between FastContainer and StableIndexVec when ids are reused.
+//! Run with `cargo run --example fast_container_demo`.
+
+use fast_container::{FastContainer, StableIndexVec};
+
+fn main() {
+    let mut fast = FastContainer::new();
+    let apple = fast.add("apple");
+    let banana = fast.add("banana");
+    fast.add("cherry");
+
+    fast.remove(apple);
+    let date = fast.add("date");
+    println!("FastContainer reuses ids: apple had id {apple}, date got id {date}");
+    println!("  looking up apple's old id now finds {:?}", fast.get(apple));
+    println!("  banana is still at id {banana}: {:?}", fast.get(banana));
+
+    let mut stable = StableIndexVec::new();
+    let apple = stable.add("apple");
+    stable.add("banana");
+    stable.add("cherry");
+
+    stable.remove(apple);
+    let date = stable.add("date");
+    println!("StableIndexVec bumps the generation: apple had {apple:?}, date got {date:?}");
+    println!("  looking up apple's old key now finds {:?}", stable.get(apple));
+
+    println!("FastContainer contents:");
+    for (idx, fruit) in fast.iter() {
+        println!("  {idx}: {fruit}");
+    }
+}
@@ -0,0 +1,212 @@
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+
+use alloc::string::ToString;
+
+use crate::{CapacityError, IndexInt, SIVKey};
+
+/// Fixed-capacity vector storing up to `N` values inline, with just the `Vec` methods `impl_stable_core!` needs
+struct ArrayVec<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    fn new() -> Self {
+        Self {
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    /// Panics if `additional` more values don't fit, since the storage can't grow
+    fn reserve(&mut self, additional: usize) {
+        assert!(additional <= N - self.len, "ArrayStableIndexVec storage is full");
+    }
+
+    fn push(&mut self, value: T) {
+        self.reserve(1);
+        self.buf[self.len].write(value);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        // the value at the old last position is initialized, and lowering len first means it's never read again
+        Some(unsafe { self.buf[self.len].assume_init_read() })
+    }
+
+    fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len, "insertion index is out of bounds");
+        self.push(value);
+        self[index..].rotate_right(1);
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        self[index..].rotate_left(1);
+        self.pop().unwrap()
+    }
+
+    fn swap_remove(&mut self, index: usize) -> T {
+        let last = self.len - 1;
+        self.swap(index, last);
+        self.pop().unwrap()
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // the first len values are initialized
+        unsafe { core::slice::from_raw_parts(self.buf.as_ptr().cast(), self.len) }
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // the first len values are initialized
+        unsafe { core::slice::from_raw_parts_mut(self.buf.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new();
+        for value in self.iter() {
+            clone.push(value.clone());
+        }
+        clone
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        // the first len values are initialized and nothing uses them after this
+        unsafe { core::ptr::drop_in_place(&mut **self as *mut [T]) }
+    }
+}
+
+/// A StableIndexVec that stores up to `N` elements entirely inline, for fixed-size pools that must never allocate.
+///
+/// `add` hands the value back in a `CapacityError` once the container is full. Unlike `InlineStableIndexVec`,
+/// it never spills to the heap.
+pub struct ArrayStableIndexVec<T, const N: usize, I: IndexInt = usize> {
+    index: ArrayVec<I, N>,
+    generations: ArrayVec<I, N>,
+    ids: ArrayVec<I, N>,
+    data: ArrayVec<T, N>,
+    generation_floor: I,
+}
+
+impl<T, const N: usize, I: IndexInt> Default for ArrayStableIndexVec<T, N, I> {
+    fn default() -> Self {
+        Self {
+            index: ArrayVec::new(),
+            generations: ArrayVec::new(),
+            ids: ArrayVec::new(),
+            data: ArrayVec::new(),
+            generation_floor: I::default(),
+        }
+    }
+}
+
+impl<T: Clone, const N: usize, I: IndexInt> Clone for ArrayStableIndexVec<T, N, I> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            generations: self.generations.clone(),
+            ids: self.ids.clone(),
+            data: self.data.clone(),
+            generation_floor: self.generation_floor,
+        }
+    }
+}
+
+impl<T: core::fmt::Debug, const N: usize, I: IndexInt> core::fmt::Debug for ArrayStableIndexVec<T, N, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug_string = f.debug_struct("ArrayStableIndexVec");
+        for (i, el) in self.data.iter().enumerate() {
+            debug_string.field(&self.ids[i].to_string(), el);
+        }
+        debug_string.finish()
+    }
+}
+
+impl<T, const N: usize> ArrayStableIndexVec<T, N> {
+    /// Creates a new empty ArrayStableIndexVec with `usize` ids
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl_stable_core!(impl [T, const N: usize, I: IndexInt] ArrayStableIndexVec<T, N, I>, add = add_unchecked);
+
+impl<T, const N: usize, I: IndexInt> ArrayStableIndexVec<T, N, I> {
+    /// Checks if another `add` would fail. Besides `N` live elements, this happens once retired ids have used up
+    /// the id storage
+    pub fn is_full(&self) -> bool {
+        let needs_new_id = self.data.len() == self.ids.len();
+        self.data.len() == N || (needs_new_id && self.index.len() == N)
+    }
+
+    /// Adds an element and returns its key, or hands it back if the container is full
+    pub fn add(&mut self, el: T) -> Result<SIVKey<I>, CapacityError<T>> {
+        if self.is_full() {
+            return Err(CapacityError { value: el });
+        }
+        Ok(self.add_unchecked(el))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_get_and_remove_work() {
+        let mut container = ArrayStableIndexVec::<&str, 3>::new();
+        let a = container.add("a").unwrap();
+        let b = container.add("b").unwrap();
+        let c = container.add("c").unwrap();
+        assert!(container.is_full());
+        assert_eq!(container.add("d").unwrap_err().into_inner(), "d");
+
+        assert_eq!(container.remove(a), Some("a"));
+        let d = container.add("d").unwrap();
+        assert_eq!(container.get(a), None);
+        assert_eq!(container.get(d), Some(&"d"));
+        assert_eq!(container.remove_stable(b), Some("b"));
+        assert_eq!(container.iter().collect::<Vec<_>>(), [(c, &"c"), (d, &"d")]);
+        assert_eq!(container.validate(), Ok(()));
+    }
+
+    #[test]
+    fn retired_ids_count_against_capacity() {
+        let mut container = ArrayStableIndexVec::<u8, 2, u16>::default();
+        let key = container.add(0).unwrap();
+        container.generations[key.id as usize] = u16::MAX - 1;
+        container.remove(key);
+
+        // the retired id still holds one of the two id slots
+        container.add(1).unwrap();
+        assert!(container.is_full());
+        assert!(container.add(2).is_err());
+        assert_eq!(container.validate(), Ok(()));
+    }
+
+    #[test]
+    fn drops_remaining_elements() {
+        let counter = std::rc::Rc::new(());
+        let mut container = ArrayStableIndexVec::<_, 4>::new();
+        let keys: Vec<_> = (0..4).map(|_| container.add(counter.clone()).unwrap()).collect();
+        container.remove(keys[1]);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 4);
+
+        let cloned = container.clone();
+        assert_eq!(std::rc::Rc::strong_count(&counter), 7);
+        drop(container);
+        drop(cloned);
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Iter, SIVKey, StableIndexVec};
+
+/// Error returned when adding to a full `BoundedStableIndexVec`. It hands the rejected value back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapacityError<T> {
+    pub(crate) value: T,
+}
+
+impl<T> CapacityError<T> {
+    /// Takes back the value that couldn't be added
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> core::fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("container is at its maximum capacity")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: core::fmt::Debug> std::error::Error for CapacityError<T> {}
+
+/// A StableIndexVec that never holds more than a fixed number of elements, for pools that must stay within a limit.
+///
+/// `add` hands the value back in a `CapacityError` instead of growing past the limit. Removing an element
+/// frees room for another one.
+#[derive(Clone, Debug)]
+pub struct BoundedStableIndexVec<T> {
+    inner: StableIndexVec<T>,
+    max: usize,
+}
+
+impl<T> BoundedStableIndexVec<T> {
+    /// Creates a new empty container that holds at most `max` elements
+    pub fn with_max_capacity(max: usize) -> Self {
+        Self {
+            inner: StableIndexVec::new(),
+            max,
+        }
+    }
+
+    /// Gets the most elements the container will hold
+    pub fn max_capacity(&self) -> usize {
+        self.max
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Checks if the container has reached its maximum capacity
+    pub fn is_full(&self) -> bool {
+        self.inner.len() >= self.max
+    }
+
+    /// Gets an optional reference to an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Gets an optional mutable reference to an element by its key
+    pub fn get_mut(&mut self, key: SIVKey) -> Option<&mut T> {
+        self.inner.get_mut(key)
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Adds an element and returns its key, or hands it back if the container is full
+    pub fn add(&mut self, el: T) -> Result<SIVKey, CapacityError<T>> {
+        if self.is_full() {
+            return Err(CapacityError { value: el });
+        }
+        Ok(self.inner.add(el))
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+        self.inner.remove(key)
+    }
+
+    /// Returns an iterator over all key-value pairs in the container
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.inner.iter()
+    }
+
+    /// Returns an iterator over the valid keys in the container
+    pub fn keys(&self) -> impl Iterator<Item = SIVKey> + '_ {
+        self.inner.keys()
+    }
+
+    /// Returns an iterator over the values in the container
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.inner.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_rejects_past_the_limit() {
+        let mut pool = BoundedStableIndexVec::with_max_capacity(2);
+        let a = pool.add("a").unwrap();
+        pool.add("b").unwrap();
+        assert!(pool.is_full());
+
+        let err = pool.add("c").unwrap_err();
+        assert_eq!(err.into_inner(), "c");
+        assert_eq!(pool.len(), 2);
+
+        pool.remove(a);
+        let c = pool.add("c").unwrap();
+        assert_eq!(pool.get(c), Some(&"c"));
+        assert_eq!(pool.max_capacity(), 2);
+    }
+
+    #[test]
+    fn zero_capacity_rejects_everything() {
+        let mut pool = BoundedStableIndexVec::with_max_capacity(0);
+        assert!(pool.add(1).is_err());
+        assert!(pool.is_empty());
+    }
+}
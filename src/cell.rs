@@ -0,0 +1,123 @@
+use core::cell::{Ref, RefCell, RefMut};
+
+use crate::{SIVKey, StableIndexVec};
+
+/// A StableIndexVec behind a `RefCell`, for single-threaded code that shares one container, e.g. through `Rc`.
+///
+/// The convenience methods borrow only for their own call and hand values out by clone or by move, so they can't
+/// conflict with each other. They panic like `RefCell` does if a guard from `borrow` or `borrow_mut` is still
+/// alive in a conflicting way; keep those guards short. A panic in `with` releases the borrow as it unwinds.
+#[derive(Default, Clone, Debug)]
+pub struct CellStableIndexVec<T> {
+    inner: RefCell<StableIndexVec<T>>,
+}
+
+impl<T> From<StableIndexVec<T>> for CellStableIndexVec<T> {
+    fn from(container: StableIndexVec<T>) -> Self {
+        Self {
+            inner: RefCell::new(container),
+        }
+    }
+}
+
+impl<T> CellStableIndexVec<T> {
+    /// Creates a new empty CellStableIndexVec
+    pub const fn new() -> Self {
+        Self {
+            inner: RefCell::new(StableIndexVec::new()),
+        }
+    }
+
+    /// Borrows the container immutably. Panics if it's mutably borrowed
+    pub fn borrow(&self) -> Ref<'_, StableIndexVec<T>> {
+        self.inner.borrow()
+    }
+
+    /// Borrows the container mutably. Panics if it's borrowed at all
+    pub fn borrow_mut(&self) -> RefMut<'_, StableIndexVec<T>> {
+        self.inner.borrow_mut()
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.borrow().len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.borrow().is_empty()
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.borrow().contains_key(key)
+    }
+
+    /// Adds an element to the container and returns its key
+    pub fn add(&self, el: T) -> SIVKey {
+        self.borrow_mut().add(el)
+    }
+
+    /// Removes an element from the container by its key. The borrow ends before the value is returned, so
+    /// dropping it may touch the container again
+    pub fn remove(&self, key: SIVKey) -> Option<T> {
+        self.borrow_mut().remove(key)
+    }
+
+    /// Runs `f` with the container mutably borrowed
+    pub fn with<R>(&self, f: impl FnOnce(&mut StableIndexVec<T>) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+
+    /// Consumes the wrapper, returning the container
+    pub fn into_inner(self) -> StableIndexVec<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Clone> CellStableIndexVec<T> {
+    /// Gets a clone of an element by its key
+    pub fn get_cloned(&self, key: SIVKey) -> Option<T> {
+        self.borrow().get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn shared_through_rc() {
+        let container = Rc::new(CellStableIndexVec::new());
+        let other = Rc::clone(&container);
+
+        let a = container.add("a");
+        let b = other.add("b");
+        assert_eq!(container.get_cloned(b), Some("b"));
+        assert_eq!(other.remove(a), Some("a"));
+        assert!(!container.contains_key(a));
+        assert_eq!(container.with(|inner| inner.len()), 1);
+    }
+
+    #[test]
+    fn borrow_is_released_after_a_panic() {
+        let container = CellStableIndexVec::new();
+        let key = container.add(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            container.with(|_| panic!("boom"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(container.get_cloned(key), Some(1));
+    }
+
+    #[test]
+    fn conflicting_borrows_panic() {
+        let container = CellStableIndexVec::new();
+        let reader = container.borrow();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| container.add(1)));
+        assert!(result.is_err());
+        drop(reader);
+        assert!(container.is_empty());
+    }
+}
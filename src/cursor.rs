@@ -0,0 +1,142 @@
+use allocator_api2::alloc::Allocator;
+
+use crate::{IndexInt, SIVKey, StableIndexVec};
+
+/// A cursor over a container's live elements that can modify, remove and add elements mid-traversal,
+/// created by `StableIndexVec::cursor_mut`.
+///
+/// The cursor walks the dense buffer front to back and visits every element that is live and ahead of it
+/// exactly once. Removing the current element swaps the last element into its place, so the cursor stays
+/// put and that element becomes current. Added elements go to the end of the buffer, so they are visited
+/// later in the same traversal. Removals through the cursor never trigger automatic compaction, so keys don't
+/// change mid-traversal; the next removal outside a cursor compacts if the threshold has been crossed.
+pub struct CursorMut<'a, T, I: IndexInt = usize, A: Allocator = allocator_api2::alloc::Global> {
+    container: &'a mut StableIndexVec<T, I, A>,
+    position: usize,
+}
+
+impl<T, I: IndexInt, A: Allocator> CursorMut<'_, T, I, A> {
+    /// Gets the key of the current element, or `None` once the traversal is finished
+    pub fn key(&self) -> Option<SIVKey<I>> {
+        (self.position < self.container.len()).then(|| self.container.key_at(self.position))
+    }
+
+    /// Gets the current element and its key, or `None` once the traversal is finished
+    pub fn current(&mut self) -> Option<(SIVKey<I>, &mut T)> {
+        let key = self.key()?;
+        Some((key, &mut self.container.data[self.position]))
+    }
+
+    /// Moves on to the next element
+    pub fn move_next(&mut self) {
+        if self.position < self.container.len() {
+            self.position += 1;
+        }
+    }
+
+    /// Removes the current element and returns it with its (now stale) key.
+    /// The element that takes its place becomes current, so don't call `move_next` afterwards
+    pub fn remove_current(&mut self) -> Option<(SIVKey<I>, T)> {
+        let key = self.key()?;
+        let el = self.container.swap_remove_key(key)?;
+        Some((key, el))
+    }
+
+    /// Adds an element to the container and returns its key. The cursor will reach it later in this traversal
+    pub fn insert(&mut self, el: T) -> SIVKey<I> {
+        self.container.add(el)
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Creates a cursor positioned at the first element, for updating, culling and spawning elements in one pass
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T, I, A> {
+        CursorMut {
+            container: self,
+            position: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_visits_each_element_once() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let mut visited = Vec::new();
+        let mut cursor = container.cursor_mut();
+        while let Some((key, el)) = cursor.current() {
+            visited.push(key);
+            if *el % 2 == 0 {
+                cursor.remove_current();
+            } else {
+                *el *= 10;
+                cursor.move_next();
+            }
+        }
+        assert_eq!(cursor.key(), None);
+
+        visited.sort();
+        assert_eq!(visited, keys);
+        assert_eq!(container.len(), 3);
+        assert_eq!(container.get(keys[0]), Some(&10));
+        assert_eq!(container.get(keys[1]), None);
+        assert_eq!(container.get(keys[4]), Some(&50));
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn cursor_visits_inserted_elements() {
+        let mut container = StableIndexVec::new();
+        container.extend_from_slice(&[3, 1]);
+
+        let mut spawned = Vec::new();
+        let mut cursor = container.cursor_mut();
+        while let Some((_, el)) = cursor.current() {
+            let value = *el;
+            if value > 1 {
+                spawned.push(cursor.insert(value - 1));
+                cursor.remove_current();
+            } else {
+                cursor.move_next();
+            }
+        }
+
+        assert_eq!(spawned.len(), 2);
+        assert_eq!(container.values_slice(), [1, 1]);
+    }
+
+    #[test]
+    fn cursor_does_not_auto_compact() {
+        let mut container = StableIndexVec::new();
+        container.set_auto_compact(Some(0.3));
+        let keys = container.add_many(0..10);
+
+        let mut cursor = container.cursor_mut();
+        let mut removed = Vec::new();
+        while let Some((_, el)) = cursor.current() {
+            if *el < 7 {
+                removed.push(cursor.remove_current().unwrap().0);
+            } else {
+                cursor.move_next();
+            }
+        }
+
+        removed.sort();
+        assert_eq!(removed, keys[..7]);
+        assert!(container.take_compaction_remap().is_empty());
+        assert_eq!(container.get(keys[9]), Some(&9));
+
+        container.remove(keys[9]);
+        let remap = container.take_compaction_remap();
+        assert_eq!(remap.len(), 2);
+        for (old, new) in remap {
+            assert_eq!(container.get(new), Some(&keys.iter().position(|&key| key == old).unwrap()));
+        }
+        assert_eq!(container.allocated_slots(), 2);
+    }
+}
@@ -0,0 +1,89 @@
+use allocator_api2::alloc::Allocator;
+
+use crate::{IndexInt, SIVKey, StableIndexVec};
+
+/// The key-by-key difference between two containers, created by `StableIndexVec::diff`.
+///
+/// Keys are compared as-is, so this is meaningful for containers that share a key space,
+/// such as a container and a clone of it that has since been modified.
+pub struct Diff<'a, T, I: IndexInt = usize, A: Allocator = allocator_api2::alloc::Global> {
+    left: &'a StableIndexVec<T, I, A>,
+    right: &'a StableIndexVec<T, I, A>,
+}
+
+impl<'a, T, I: IndexInt, A: Allocator> Diff<'a, T, I, A> {
+    /// Returns an iterator over the entries whose key is only live in the first container
+    pub fn only_in_self(&self) -> impl Iterator<Item = (SIVKey<I>, &'a T)> + 'a {
+        let right = self.right;
+        self.left.iter().filter(move |(key, _)| !right.contains_key(*key))
+    }
+
+    /// Returns an iterator over the entries whose key is only live in the second container
+    pub fn only_in_other(&self) -> impl Iterator<Item = (SIVKey<I>, &'a T)> + 'a {
+        let left = self.left;
+        self.right.iter().filter(move |(key, _)| !left.contains_key(*key))
+    }
+
+    /// Returns an iterator over the keys live in both containers along with both values
+    pub fn in_both(&self) -> impl Iterator<Item = (SIVKey<I>, &'a T, &'a T)> + 'a {
+        let right = self.right;
+        self.left.iter().filter_map(move |(key, left)| Some((key, left, right.get(key)?)))
+    }
+}
+
+impl<'a, T: PartialEq, I: IndexInt, A: Allocator> Diff<'a, T, I, A> {
+    /// Returns an iterator over the keys live in both containers whose values differ
+    pub fn changed(&self) -> impl Iterator<Item = (SIVKey<I>, &'a T, &'a T)> + 'a {
+        self.in_both().filter(|(_, left, right)| left != right)
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Compares this container with another by key, for change detection and incremental sync
+    pub fn diff<'a>(&'a self, other: &'a Self) -> Diff<'a, T, I, A> {
+        Diff {
+            left: self,
+            right: other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_splits_keys_by_where_they_live() {
+        let mut before = StableIndexVec::new();
+        let removed = before.add("removed");
+        let changed = before.add("old");
+        let unchanged = before.add("same");
+
+        let mut after = before.clone();
+        after.remove(removed);
+        *after.as_mut_slice().iter_mut().find(|value| **value == "old").unwrap() = "new";
+        let added = after.add("added");
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.only_in_self().collect::<Vec<_>>(), [(removed, &"removed")]);
+        assert_eq!(diff.only_in_other().collect::<Vec<_>>(), [(added, &"added")]);
+        assert_eq!(diff.changed().collect::<Vec<_>>(), [(changed, &"old", &"new")]);
+
+        let mut in_both: Vec<_> = diff.in_both().map(|(key, _, _)| key).collect();
+        in_both.sort();
+        assert_eq!(in_both, [changed, unchanged]);
+    }
+
+    #[test]
+    fn diff_of_identical_containers_is_empty() {
+        let mut container = StableIndexVec::new();
+        container.add(1);
+        container.add(2);
+
+        let diff = container.diff(&container);
+        assert_eq!(diff.only_in_self().count(), 0);
+        assert_eq!(diff.only_in_other().count(), 0);
+        assert_eq!(diff.changed().count(), 0);
+        assert_eq!(diff.in_both().count(), 2);
+    }
+}
@@ -0,0 +1,419 @@
+use allocator_api2::vec::Vec;
+
+use crate::{SIVKey, StableIndexVec};
+
+/// A lighter-weight container without generations.
+///
+/// Like StableIndexVec it keeps values densely packed and hands out ids that stay valid while their element is
+/// live, but it doesn't track generations. A removed id is handed out again by the next `add`, so an id kept
+/// after its element was removed can silently refer to a different element. Use it when that's acceptable.
+#[derive(Clone)]
+pub struct FastContainer<T> {
+    index: Vec<usize>,
+    /// `ids[..data.len()]` are the ids of the live elements, `ids[data.len()..]` are free ids waiting to be reused
+    ids: Vec<usize>,
+    data: Vec<T>,
+}
+
+impl<T> Default for FastContainer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for FastContainer<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.ids.iter().zip(self.data.iter())).finish()
+    }
+}
+
+impl<T> FastContainer<T> {
+    /// Creates a new empty FastContainer
+    pub fn new() -> Self {
+        Self {
+            index: Vec::new(),
+            ids: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Creates a new empty FastContainer with room for `capacity` elements before reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            index: Vec::with_capacity(capacity),
+            ids: Vec::with_capacity(capacity),
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Gets the number of elements the container can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn data_index(&self, idx: usize) -> Option<usize> {
+        let data_index = *self.index.get(idx)?;
+        (data_index < self.data.len()).then_some(data_index)
+    }
+
+    /// Gets an optional reference to an element by its id
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let data_index = self.data_index(idx)?;
+        self.data.get(data_index)
+    }
+
+    /// Gets an optional mutable reference to an element by its id
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let data_index = self.data_index(idx)?;
+        self.data.get_mut(data_index)
+    }
+
+    /// Adds an element to the container and returns its id, reusing a removed id if there is one
+    pub fn add(&mut self, el: T) -> usize {
+        let data_len = self.data.len();
+        if data_len == self.ids.len() {
+            let id = self.index.len();
+            self.index.push(data_len);
+            self.ids.push(id);
+        }
+
+        self.data.push(el);
+        self.ids[data_len]
+    }
+
+    /// Removes an element from the container by its id
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        let data_index = self.data_index(idx)?;
+
+        let last_index = self.data.len() - 1;
+        if data_index < last_index {
+            self.data.swap(data_index, last_index);
+            self.ids.swap(data_index, last_index);
+            self.index[self.ids[data_index]] = data_index;
+            self.index[self.ids[last_index]] = last_index;
+        }
+
+        self.data.pop()
+    }
+
+    /// Removes every element while keeping the allocated ids for reuse.
+    /// Outstanding ids resolve to nothing afterwards, but like any removed id they are handed out again
+    /// by later `add` calls and will then refer to the new elements
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns an iterator over the id and a reference to each element in the container
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.ids.iter().copied().zip(self.data.iter())
+    }
+}
+
+impl<T> FastContainer<T> {
+    /// Converts a StableIndexVec into a FastContainer, dropping the generations.
+    /// Every element keeps its id, and the returned pairs map each old key to its id. The values are moved, not cloned.
+    /// Ids the StableIndexVec had retired become free again, since without generations there's nothing to exhaust
+    pub fn from_stable(container: StableIndexVec<T>) -> (Self, alloc::vec::Vec<(SIVKey, usize)>) {
+        let mapping = container.ids[..container.data.len()]
+            .iter()
+            .map(|&id| (SIVKey::new(id, container.generations[id]), id))
+            .collect();
+
+        let mut fast = Self {
+            index: container.index,
+            ids: container.ids,
+            data: container.data,
+        };
+        for id in 0..fast.index.len() {
+            if fast.index[id] == usize::MAX {
+                fast.index[id] = fast.ids.len();
+                fast.ids.push(id);
+            }
+        }
+        (fast, mapping)
+    }
+}
+
+impl<T> StableIndexVec<T> {
+    /// Converts a FastContainer into a StableIndexVec, starting every id at generation 0.
+    /// Every element keeps its id, and the returned pairs map each old id to its new key. The values are moved, not cloned
+    pub fn from_fast(container: FastContainer<T>) -> (Self, alloc::vec::Vec<(usize, SIVKey)>) {
+        let mapping = container.ids[..container.data.len()].iter().map(|&id| (id, SIVKey::new(id, 0))).collect();
+
+        let mut generations = Vec::with_capacity(container.index.len());
+        generations.resize(container.index.len(), 0);
+        let stable = Self {
+            index: container.index,
+            generations,
+            ids: container.ids,
+            data: container.data,
+            generation_floor: 0,
+            auto_compact_threshold: None,
+            compaction_remap: alloc::vec::Vec::new(),
+        };
+        (stable, mapping)
+    }
+}
+
+impl<T: PartialEq> FastContainer<T> {
+    /// Checks if the given element exists in the container
+    pub fn contains(&self, el: &T) -> bool {
+        self.data.contains(el)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn len_is_correct() {
+        let mut container = FastContainer::<isize>::new();
+        assert!(container.is_empty());
+
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        let idx3 = container.add(3);
+        assert_eq!(container.len(), 3);
+        assert!(!container.is_empty());
+
+        container.remove(idx2);
+        assert_eq!(container.len(), 2);
+
+        container.remove(idx1);
+        container.remove(idx3);
+        assert_eq!(container.len(), 0);
+        assert!(container.is_empty());
+    }
+
+    #[test]
+    fn add_and_get_work() {
+        let mut container = FastContainer::<isize>::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        let idx3 = container.add(3);
+
+        assert_eq!(container.get(idx1), Some(&1));
+        assert_eq!(container.get(idx2), Some(&2));
+        assert_eq!(container.get(idx3), Some(&3));
+        assert_eq!(container.get(42), None);
+    }
+
+    #[test]
+    fn get_mut_modifies_element() {
+        let mut container = FastContainer::<isize>::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        container.remove(idx1);
+
+        *container.get_mut(idx2).unwrap() += 40;
+        assert_eq!(container.get(idx2), Some(&42));
+        assert_eq!(container.get_mut(idx1), None);
+    }
+
+    #[test]
+    fn iter_yields_live_elements() {
+        let mut container = FastContainer::new();
+        container.add(1);
+        let idx2 = container.add(2);
+        container.add(3);
+        container.remove(idx2);
+
+        let mut values: Vec<_> = container.iter().map(|(_, value)| *value).collect();
+        values.sort();
+        assert_eq!(values, [1, 3]);
+
+        for (idx, value) in container.iter() {
+            assert_eq!(container.get(idx), Some(value));
+        }
+        assert_eq!(FastContainer::<isize>::new().iter().count(), 0);
+    }
+
+    #[test]
+    fn contains_checks_live_values() {
+        let mut container = FastContainer::new();
+        let idx1 = container.add(1);
+        container.add(2);
+        container.remove(idx1);
+
+        assert!(!container.contains(&1));
+        assert!(container.contains(&2));
+    }
+
+    #[test]
+    fn works_without_partial_eq() {
+        struct NotComparable(isize);
+
+        let mut container = FastContainer::new();
+        let idx = container.add(NotComparable(7));
+        assert_eq!(container.get(idx).map(|el| el.0), Some(7));
+        assert!(container.remove(idx).is_some());
+    }
+
+    #[test]
+    fn clear_removes_everything_and_reuses_ids() {
+        let mut container = FastContainer::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+
+        container.clear();
+        assert!(container.is_empty());
+        assert_eq!(container.get(idx1), None);
+        assert_eq!(container.get(idx2), None);
+
+        let idx3 = container.add(3);
+        let idx4 = container.add(4);
+        let idx5 = container.add(5);
+        assert!([idx1, idx2].contains(&idx3));
+        assert!([idx1, idx2].contains(&idx4));
+        assert_eq!(container.get(idx3), Some(&3));
+        assert_eq!(container.get(idx4), Some(&4));
+        assert_eq!(container.get(idx5), Some(&5));
+        assert_eq!(container.len(), 3);
+    }
+
+    #[test]
+    fn with_capacity_preallocates() {
+        let mut container = FastContainer::with_capacity(64);
+        assert!(container.capacity() >= 64);
+        assert!(container.is_empty());
+
+        let data_ptr = container.data.as_ptr();
+        let index_ptr = container.index.as_ptr();
+        for i in 0..64 {
+            container.add(i);
+        }
+        assert_eq!(container.data.as_ptr(), data_ptr);
+        assert_eq!(container.index.as_ptr(), index_ptr);
+        assert!(container.capacity() >= 64);
+    }
+
+    #[test]
+    fn converts_to_stable_and_back() {
+        let mut fast = FastContainer::new();
+        let idx1 = fast.add(String::from("a"));
+        let idx2 = fast.add(String::from("b"));
+        let idx3 = fast.add(String::from("c"));
+        fast.remove(idx2);
+        let data_ptr = fast.data.as_ptr();
+
+        let (mut stable, mapping) = StableIndexVec::from_fast(fast);
+        assert_eq!(stable.validate(), Ok(()));
+        assert_eq!(stable.len(), 2);
+        assert_eq!(stable.as_ptr(), data_ptr);
+        for (idx, key) in &mapping {
+            assert!([idx1, idx3].contains(idx));
+            assert_eq!(stable.get(*key).map(String::as_str), Some(["a", "", "c"][*idx]));
+        }
+
+        let key4 = stable.add(String::from("d"));
+        let (fast, mapping) = FastContainer::from_stable(stable);
+        assert_eq!(fast.len(), 3);
+        assert_eq!(fast.data.as_ptr(), data_ptr);
+        for (key, idx) in &mapping {
+            assert_eq!(key.id, *idx);
+        }
+        assert_eq!(fast.get(key4.id).map(String::as_str), Some("d"));
+        assert_eq!(fast.get(idx1).map(String::as_str), Some("a"));
+        assert_eq!(fast.get(idx3).map(String::as_str), Some("c"));
+    }
+
+    #[test]
+    fn from_stable_frees_retired_ids() {
+        let pairs = [(SIVKey::new(0, usize::MAX), "x"), (SIVKey::new(1, 0), "y")];
+        let mut stable = StableIndexVec::<&str>::from_keyed_pairs(pairs).unwrap();
+        stable.remove(SIVKey::new(0, usize::MAX));
+
+        let (mut fast, _) = FastContainer::from_stable(stable);
+        assert_eq!(fast.ids.len(), fast.index.len());
+        let added = fast.add("new");
+        assert_ne!(added, 1);
+        assert_eq!(fast.get(added), Some(&"new"));
+        assert_eq!(fast.get(1), Some(&"y"));
+
+        let fresh = fast.add("fresh");
+        assert_eq!(fresh, 2);
+        assert_eq!(fast.get(1), Some(&"y"));
+    }
+
+    #[test]
+    fn ids_are_stable_when_removing_from_start() {
+        let mut container = FastContainer::<isize>::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        let idx3 = container.add(3);
+        let idx4 = container.add(4);
+
+        assert_eq!(container.remove(idx1), Some(1));
+
+        assert_eq!(container.get(idx1), None);
+        assert_eq!(container.get(idx2), Some(&2));
+        assert_eq!(container.get(idx3), Some(&3));
+        assert_eq!(container.get(idx4), Some(&4));
+    }
+
+    #[test]
+    fn ids_are_stable_when_removing_from_middle() {
+        let mut container = FastContainer::<isize>::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        let idx3 = container.add(3);
+        let idx4 = container.add(4);
+
+        assert_eq!(container.remove(idx2), Some(2));
+
+        assert_eq!(container.get(idx1), Some(&1));
+        assert_eq!(container.get(idx2), None);
+        assert_eq!(container.get(idx3), Some(&3));
+        assert_eq!(container.get(idx4), Some(&4));
+    }
+
+    #[test]
+    fn ids_are_stable_when_removing_from_end() {
+        let mut container = FastContainer::<isize>::new();
+        let idx1 = container.add(1);
+        let idx2 = container.add(2);
+        let idx3 = container.add(3);
+        let idx4 = container.add(4);
+
+        assert_eq!(container.remove(idx4), Some(4));
+
+        assert_eq!(container.get(idx1), Some(&1));
+        assert_eq!(container.get(idx2), Some(&2));
+        assert_eq!(container.get(idx3), Some(&3));
+        assert_eq!(container.get(idx4), None);
+    }
+
+    #[test]
+    fn removed_ids_are_reused() {
+        let mut container = FastContainer::<isize>::new();
+        container.add(1);
+        let idx2 = container.add(2);
+        container.add(3);
+
+        container.remove(idx2);
+        let idx4 = container.add(4);
+
+        assert_eq!(idx4, idx2);
+        assert_eq!(container.get(idx2), Some(&4));
+    }
+
+    #[test]
+    fn removing_valid_id_twice_returns_none() {
+        let mut container = FastContainer::<isize>::new();
+        let idx = container.add(1);
+
+        assert_eq!(container.remove(idx), Some(1));
+        assert_eq!(container.remove(idx), None);
+    }
+}
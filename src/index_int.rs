@@ -0,0 +1,55 @@
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Integer type used for a container's ids and generations.
+///
+/// Implemented for `u16`, `u32`, `u64` and `usize`, except for types wider than `usize` on the target, so every
+/// value, including the `MAX` sentinel, converts to `usize` losslessly. Smaller types shrink the container's
+/// metadata and its keys (`StableIndexVec<T, u32>` uses half the metadata memory of the default on 64-bit targets)
+/// but limit how many ids the container can hand out and how many times an id can be reused before it's retired.
+pub trait IndexInt:
+    sealed::Sealed + Copy + Default + Eq + Ord + core::hash::Hash + core::fmt::Debug + core::fmt::Display
+{
+    /// The largest value of the type, used as the retired sentinel and the exhausted generation
+    const MAX: Self;
+
+    /// Converts from `usize`, panicking if the value doesn't fit
+    fn from_usize(value: usize) -> Self;
+
+    /// Converts to `usize` for indexing, panicking if the value doesn't fit
+    fn to_usize(self) -> usize;
+
+    /// Adds one, returning `None` on overflow
+    fn checked_increment(self) -> Option<Self>;
+}
+
+macro_rules! impl_index_int {
+    ($($int:ty),*) => {
+        $(
+            impl sealed::Sealed for $int {}
+
+            impl IndexInt for $int {
+                const MAX: Self = <$int>::MAX;
+
+                fn from_usize(value: usize) -> Self {
+                    Self::try_from(value).expect(concat!("value does not fit in ", stringify!($int)))
+                }
+
+                fn to_usize(self) -> usize {
+                    usize::try_from(self).expect("value does not fit in usize")
+                }
+
+                fn checked_increment(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+            }
+        )*
+    };
+}
+
+impl_index_int!(u16, usize);
+#[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+impl_index_int!(u32);
+#[cfg(target_pointer_width = "64")]
+impl_index_int!(u64);
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{Iter, SIVKey, StableIndexVec};
+
+/// A StableIndexVec that also indexes its elements by value, so `contains`, `key_of` and `get_or_add` are O(1).
+///
+/// The index keeps a clone of every distinct value plus the keys holding it, so it costs extra memory and needs
+/// `T: Eq + Hash + Clone`. Values can't be mutated in place, since that would desync the index; use `replace`.
+#[derive(Clone, Debug)]
+pub struct IndexedStableIndexVec<T> {
+    inner: StableIndexVec<T>,
+    /// Every live key holding each value, oldest first
+    by_value: HashMap<T, Vec<SIVKey>>,
+}
+
+impl<T: Eq + Hash + Clone> Default for IndexedStableIndexVec<T> {
+    fn default() -> Self {
+        Self {
+            inner: StableIndexVec::new(),
+            by_value: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone> IndexedStableIndexVec<T> {
+    /// Creates a new empty IndexedStableIndexVec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Gets an optional reference to an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Checks if the given element exists in the container. This is O(1)
+    pub fn contains(&self, el: &T) -> bool {
+        self.by_value.contains_key(el)
+    }
+
+    /// Gets the key of the oldest element equal to `value`. This is O(1)
+    pub fn key_of(&self, value: &T) -> Option<SIVKey> {
+        self.by_value.get(value)?.first().copied()
+    }
+
+    /// Adds an element to the container and returns its key
+    pub fn add(&mut self, el: T) -> SIVKey {
+        let key = self.inner.add(el.clone());
+        self.by_value.entry(el).or_default().push(key);
+        key
+    }
+
+    /// Gets the key of a value equal to `value` if there is one, otherwise adds `value` and returns its new key.
+    /// This is O(1)
+    pub fn get_or_add(&mut self, value: T) -> SIVKey {
+        match self.key_of(&value) {
+            Some(key) => key,
+            None => self.add(value),
+        }
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+        let el = self.inner.remove(key)?;
+        self.unindex(&el, key);
+        Some(el)
+    }
+
+    /// Replaces the value stored under a live key, returning the old value. The key stays valid
+    pub fn replace(&mut self, key: SIVKey, el: T) -> Option<T> {
+        let slot = self.inner.get_mut(key)?;
+        let old = core::mem::replace(slot, el.clone());
+        self.unindex(&old, key);
+        self.by_value.entry(el).or_default().push(key);
+        Some(old)
+    }
+
+    /// Returns an iterator over all key-value pairs in the container
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.inner.iter()
+    }
+
+    /// Returns an iterator over the valid keys in the container
+    pub fn keys(&self) -> impl Iterator<Item = SIVKey> + '_ {
+        self.inner.keys()
+    }
+
+    /// Returns an iterator over the values in the container
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.inner.values()
+    }
+
+    /// Drops `key` from the index entry of `el`, and the entry itself once no keys hold the value
+    fn unindex(&mut self, el: &T, key: SIVKey) {
+        if let Some(keys) = self.by_value.get_mut(el) {
+            keys.retain(|&k| k != key);
+            if keys.is_empty() {
+                self.by_value.remove(el);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_by_value() {
+        let mut container = IndexedStableIndexVec::new();
+        let a = container.add("a");
+        let b = container.add("b");
+        let a2 = container.add("a");
+
+        assert!(container.contains(&"a"));
+        assert_eq!(container.key_of(&"a"), Some(a));
+        assert_eq!(container.get_or_add("b"), b);
+
+        assert_eq!(container.remove(a), Some("a"));
+        assert_eq!(container.key_of(&"a"), Some(a2));
+        container.remove(a2);
+        assert!(!container.contains(&"a"));
+
+        let c = container.get_or_add("c");
+        assert_eq!(container.get(c), Some(&"c"));
+        assert_eq!(container.len(), 2);
+    }
+
+    #[test]
+    fn replace_moves_index_entry() {
+        let mut container = IndexedStableIndexVec::new();
+        let key = container.add(1);
+
+        assert_eq!(container.replace(key, 2), Some(1));
+        assert!(!container.contains(&1));
+        assert_eq!(container.key_of(&2), Some(key));
+        assert_eq!(container.get(key), Some(&2));
+
+        container.remove(key);
+        assert_eq!(container.replace(key, 3), None);
+        assert!(container.is_empty());
+    }
+}
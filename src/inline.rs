@@ -0,0 +1,110 @@
+use alloc::string::ToString;
+use smallvec::SmallVec;
+
+use crate::IndexInt;
+
+/// A StableIndexVec whose storage lives inline until it holds more than `N` elements.
+///
+/// Each of the four backing vectors is a `SmallVec` with room for `N` entries, so a container that
+/// never grows past `N` live elements never touches the heap. Past that it spills and behaves like the heap type.
+pub struct InlineStableIndexVec<T, const N: usize, I: IndexInt = usize> {
+    index: SmallVec<[I; N]>,
+    generations: SmallVec<[I; N]>,
+    ids: SmallVec<[I; N]>,
+    data: SmallVec<[T; N]>,
+    generation_floor: I,
+}
+
+impl<T, const N: usize, I: IndexInt> Default for InlineStableIndexVec<T, N, I> {
+    fn default() -> Self {
+        Self {
+            index: SmallVec::new(),
+            generations: SmallVec::new(),
+            ids: SmallVec::new(),
+            data: SmallVec::new(),
+            generation_floor: I::default(),
+        }
+    }
+}
+
+impl<T: Clone, const N: usize, I: IndexInt> Clone for InlineStableIndexVec<T, N, I> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            generations: self.generations.clone(),
+            ids: self.ids.clone(),
+            data: self.data.clone(),
+            generation_floor: self.generation_floor,
+        }
+    }
+}
+
+impl<T: core::fmt::Debug, const N: usize, I: IndexInt> core::fmt::Debug for InlineStableIndexVec<T, N, I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug_string = f.debug_struct("InlineStableIndexVec");
+        for (i, el) in self.data.iter().enumerate() {
+            debug_string.field(&self.ids[i].to_string(), el);
+        }
+        debug_string.finish()
+    }
+}
+
+impl<T, const N: usize> InlineStableIndexVec<T, N> {
+    /// Creates a new empty InlineStableIndexVec with `usize` ids
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl_stable_core!(impl [T, const N: usize, I: IndexInt] InlineStableIndexVec<T, N, I>);
+
+impl<T, const N: usize, I: IndexInt> InlineStableIndexVec<T, N, I> {
+    /// Checks if any of the backing storage has moved to the heap
+    pub fn spilled(&self) -> bool {
+        self.index.spilled() || self.generations.spilled() || self.ids.spilled() || self.data.spilled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_get_and_remove_work() {
+        let mut container = InlineStableIndexVec::<isize, 4>::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+
+        assert_eq!(container.remove(key2), Some(2));
+        let key4 = container.add(4);
+
+        assert_eq!(container.get(key1), Some(&1));
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key3), Some(&3));
+        assert_eq!(container.get(key4), Some(&4));
+        assert_eq!(container.len(), 3);
+        assert_eq!(container.validate(), Ok(()));
+
+        for (key, value) in container.iter() {
+            assert_eq!(container.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn does_not_allocate_below_inline_capacity() {
+        let mut container = InlineStableIndexVec::<isize, 4>::new();
+        let mut keys: Vec<_> = (0..4).map(|i| container.add(i)).collect();
+        assert!(!container.spilled());
+
+        // cycling ids doesn't grow the metadata past the peak live count
+        for i in 0..100 {
+            let value = container.remove(keys[i % 4]).unwrap();
+            keys[i % 4] = container.add(value);
+        }
+        assert!(!container.spilled());
+
+        container.add(4);
+        assert!(container.spilled());
+    }
+}
@@ -1,164 +1,1091 @@
-#[derive(Default, Clone)]
-pub struct StableIndexVec<T> {
-    index: Vec<usize>,
-    generations: Vec<usize>,
-    ids: Vec<usize>,
-    data: Vec<T>,
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+
+extern crate alloc;
+
+#[macro_use]
+mod stable_core;
+mod array;
+mod bounded;
+mod cell;
+mod cursor;
+mod diff;
+mod fast;
+mod index_int;
+#[cfg(feature = "value-index")]
+mod indexed;
+#[cfg(feature = "smallvec")]
+mod inline;
+mod lru;
+mod ordered;
+mod ordered_by;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "serde")]
+mod serialization;
+mod sidecar;
+#[cfg(feature = "arc-swap")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "serde")]
+pub mod values_only;
+
+pub use array::ArrayStableIndexVec;
+pub use bounded::{BoundedStableIndexVec, CapacityError};
+pub use cell::CellStableIndexVec;
+pub use cursor::CursorMut;
+pub use diff::Diff;
+pub use fast::FastContainer;
+pub use index_int::IndexInt;
+#[cfg(feature = "value-index")]
+pub use indexed::IndexedStableIndexVec;
+#[cfg(feature = "smallvec")]
+pub use inline::InlineStableIndexVec;
+pub use lru::LruStableIndexVec;
+pub use ordered::OrderedStableIndexVec;
+pub use ordered_by::OrderedBy;
+#[cfg(feature = "serde")]
+pub use serialization::FORMAT_VERSION;
+pub use sidecar::SidecarMap;
+#[cfg(feature = "arc-swap")]
+pub use snapshot::ArcSwapStableIndexVec;
+#[cfg(feature = "std")]
+pub use sync::{ShardedStableIndexVec, SharedStableIndexVec, SyncStableIndexVec};
+
+use alloc::string::ToString;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec;
+
+/// A container that hands out stable keys for its elements while keeping the elements densely packed.
+///
+/// Live values are always stored contiguously, with no holes: removal swaps the last value into the gap. So
+/// `values_slice()` always holds exactly `len()` values, which can be uploaded or copied as one block.
+///
+/// The id and generation width defaults to `usize`. Pass a smaller [`IndexInt`] such as `u32`
+/// (`StableIndexVec::<T, u32>::default()`) to shrink the metadata and keys when the container stays small.
+///
+/// All four backing vectors are allocated with `A`, which defaults to the global allocator. The `Allocator` trait
+/// comes from `allocator-api2`; enable the `nightly` feature to use allocators written against the unstable std trait.
+pub struct StableIndexVec<T, I: IndexInt = usize, A: Allocator = Global> {
+    index: Vec<I, A>,
+    generations: Vec<I, A>,
+    /// `ids[..data.len()]` are the ids of the live elements, `ids[data.len()..]` is the free list of
+    /// removed ids that `add` recycles (with their bumped generation) before allocating a new one
+    ids: Vec<I, A>,
+    data: Vec<T, A>,
+    /// Generation given to newly allocated ids. `compact` raises it past every generation it has handed out
+    /// so that renumbered ids can never match a key from before the compaction
+    generation_floor: I,
+    /// Fragmentation above which `remove` compacts the container. `None` (the default) never compacts automatically
+    auto_compact_threshold: Option<f64>,
+    /// Old-to-new key pairs from automatic compactions that haven't been collected by `take_compaction_remap` yet
+    compaction_remap: alloc::vec::Vec<(SIVKey<I>, SIVKey<I>)>,
 }
 
-#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct SIVKey {
-    id: usize,
-    generation: usize,
+impl<T, I: IndexInt, A: Allocator + Clone + Default> Default for StableIndexVec<T, I, A> {
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+/// A clone honors every key of the original: live keys resolve to equal values, stale keys stay stale, and both
+/// containers hand out the same keys for later adds. Code that clones a container and keeps using its keys
+/// relies on this, so any change to the free list has to preserve it
+impl<T: Clone, I: IndexInt, A: Allocator + Clone> Clone for StableIndexVec<T, I, A> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            generations: self.generations.clone(),
+            ids: self.ids.clone(),
+            data: self.data.clone(),
+            generation_floor: self.generation_floor,
+            auto_compact_threshold: self.auto_compact_threshold,
+            compaction_remap: self.compaction_remap.clone(),
+        }
+    }
+
+    /// Reuses this container's existing allocations (and the values' own, through `T::clone_from`)
+    /// instead of allocating fresh ones, which matters for containers that are cloned every frame
+    fn clone_from(&mut self, source: &Self) {
+        self.index.clone_from(&source.index);
+        self.generations.clone_from(&source.generations);
+        self.ids.clone_from(&source.ids);
+        self.data.clone_from(&source.data);
+        self.generation_floor = source.generation_floor;
+        self.auto_compact_threshold = source.auto_compact_threshold;
+        self.compaction_remap.clone_from(&source.compaction_remap);
+    }
 }
 
-impl SIVKey {
-    pub fn new(id: usize, generation: usize) -> Self {
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SIVKey<I: IndexInt = usize> {
+    id: I,
+    generation: I,
+}
+
+impl<I: IndexInt> SIVKey<I> {
+    pub fn new(id: I, generation: I) -> Self {
         Self { id, generation }
     }
 }
 
-impl<T: std::fmt::Debug> std::fmt::Debug for StableIndexVec<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Error returned by `StableIndexVec::from_keyed_pairs` when a key's id is already taken by an earlier pair,
+/// is `I::MAX`, which no container can hand out, or is too large to allocate metadata for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyCollision<I: IndexInt = usize> {
+    pub key: SIVKey<I>,
+}
+
+impl<I: IndexInt> core::fmt::Display for KeyCollision<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "key {:?} collides with another key or can't be represented", self.key)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: IndexInt> std::error::Error for KeyCollision<I> {}
+
+/// Error returned by `StableIndexVec::from_indexed` when an id is given twice, is too large for the container's
+/// id type, or is too large to allocate metadata for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdError {
+    pub id: usize,
+}
+
+impl core::fmt::Display for IdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "id {} is duplicated or can't be represented", self.id)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IdError {}
+
+/// Error returned by `StableIndexVec::remap_ids` when the mapping sends two ids to the same `id`, or produces an
+/// id too large for the container's id type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemapError {
+    pub id: usize,
+}
+
+impl core::fmt::Display for RemapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "id mapping produces {} more than once or can't represent it", self.id)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RemapError {}
+
+/// `{:?}` labels each value by its id. The alternate form `{:#?}` labels them as `id@generation`
+/// instead, which helps when chasing stale keys
+impl<T: core::fmt::Debug, I: IndexInt, A: Allocator> core::fmt::Debug for StableIndexVec<T, I, A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let alternate = f.alternate();
         let mut debug_string = f.debug_struct("StableIndexVec");
-        for (i, el) in self.data.iter().enumerate() {
-            debug_string.field(&self.ids[i].to_string(), el);
+        for (key, el) in self.iter() {
+            if alternate {
+                debug_string.field(&alloc::format!("{}@{}", key.id, key.generation), el);
+            } else {
+                debug_string.field(&key.id.to_string(), el);
+            }
         }
         debug_string.finish()
     }
 }
 
-impl<T> StableIndexVec<T> where T: PartialEq {
-    /// Creates a new empty StableIndexVec
-    pub fn new() -> Self {
+/// Two containers are equal when they hold the same set of (key, value) pairs, regardless of internal order
+impl<T: PartialEq, I: IndexInt, A: Allocator> PartialEq for StableIndexVec<T, I, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<T: Eq, I: IndexInt, A: Allocator> Eq for StableIndexVec<T, I, A> {}
+
+/// Hashes the container's `content_hash`, so the hash doesn't depend on internal order and agrees with `PartialEq`.
+/// Requires `T: Hash`
+#[cfg(feature = "std")]
+impl<T: core::hash::Hash, I: IndexInt, A: Allocator> core::hash::Hash for StableIndexVec<T, I, A> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.content_hash());
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: core::hash::Hash, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Hashes every live (key, value) pair on its own with a `DefaultHasher` and XORs the results together along
+    /// with the length, so internal order doesn't matter. Equal containers give equal hashes, so differing hashes
+    /// show two snapshots differ without running `diff`
+    pub fn content_hash(&self) -> u64 {
+        use core::hash::{Hash, Hasher};
+
+        let combined = self.iter().fold(0u64, |combined, (key, el)| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            key.hash(&mut hasher);
+            el.hash(&mut hasher);
+            combined ^ hasher.finish()
+        });
+        let mut hasher = std::hash::DefaultHasher::new();
+        hasher.write_usize(self.data.len());
+        hasher.write_u64(combined);
+        hasher.finish()
+    }
+}
+
+impl<T> StableIndexVec<T> {
+    /// Creates a new empty StableIndexVec with `usize` ids.
+    /// Use `StableIndexVec::<T, I>::default()` to pick a different index type. This is a `const fn`, so it can
+    /// initialize `const` and `static` items
+    pub const fn new() -> Self {
         Self {
             index: Vec::new(),
             generations: Vec::new(),
             ids: Vec::new(),
             data: Vec::new(),
+            generation_floor: 0,
+            auto_compact_threshold: None,
+            compaction_remap: alloc::vec::Vec::new(),
+        }
+    }
+
+    /// Creates a new empty StableIndexVec with room for `capacity` elements before reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator + Clone> StableIndexVec<T, I, A> {
+    /// Creates a new empty StableIndexVec whose backing vectors allocate with `alloc`
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            index: Vec::new_in(alloc.clone()),
+            generations: Vec::new_in(alloc.clone()),
+            ids: Vec::new_in(alloc.clone()),
+            data: Vec::new_in(alloc),
+            generation_floor: I::default(),
+            auto_compact_threshold: None,
+            compaction_remap: alloc::vec::Vec::new(),
+        }
+    }
+
+    /// Creates a new empty StableIndexVec with room for `capacity` elements, allocating with `alloc`
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self {
+            index: Vec::with_capacity_in(capacity, alloc.clone()),
+            generations: Vec::with_capacity_in(capacity, alloc.clone()),
+            ids: Vec::with_capacity_in(capacity, alloc.clone()),
+            data: Vec::with_capacity_in(capacity, alloc),
+            generation_floor: I::default(),
+            auto_compact_threshold: None,
+            compaction_remap: alloc::vec::Vec::new(),
         }
     }
+}
+
+impl<T, I: IndexInt, A: Allocator + Clone + Default> StableIndexVec<T, I, A> {
+    /// Rebuilds a container that honors the given keys, so every key resolves to its value again.
+    /// Values are stored in the order given. Ids missing from `pairs`, and ids added later, start past the newest
+    /// generation in `pairs`, so stale keys from before the rebuild can't resolve to new elements.
+    /// The metadata grows to the largest id given, so sparse ids cost memory; an id whose metadata can't be
+    /// allocated is rejected rather than aborting
+    pub fn from_keyed_pairs(pairs: impl IntoIterator<Item = (SIVKey<I>, T)>) -> Result<Self, KeyCollision<I>> {
+        let mut container = Self::default();
+        let mut newest = None;
+        for (key, el) in pairs {
+            let id = key.id.to_usize();
+            if key.id == I::MAX || container.index.get(id).is_some_and(|&slot| slot != I::MAX) {
+                return Err(KeyCollision { key });
+            }
+            if id >= container.index.len() {
+                let additional = id + 1 - container.index.len();
+                let reserved = container.index.try_reserve(additional).is_ok()
+                    && container.generations.try_reserve(additional).is_ok();
+                if !reserved {
+                    return Err(KeyCollision { key });
+                }
+                container.index.resize(id + 1, I::MAX);
+                container.generations.resize(id + 1, I::MAX);
+            }
+            container.index[id] = I::from_usize(container.data.len());
+            container.generations[id] = key.generation;
+            container.ids.push(key.id);
+            container.data.push(el);
+            newest = newest.max(Some(key.generation));
+        }
 
-    fn data_index(&self, key: SIVKey) -> Option<usize> {
-        let data_index = self.index.get(key.id)?;
-        match self.generations.get(*data_index) {
-            Some(generation) if *generation == key.generation => Some(*data_index),
-            _ => None,
+        // if the newest generation is exhausted, no generation is safe for the missing ids, so they're retired
+        let floor = newest.map_or(Some(I::default()), IndexInt::checked_increment);
+        container.generation_floor = floor.unwrap_or(I::MAX);
+        if let Some(floor) = floor {
+            for id in 0..container.index.len() {
+                if container.index[id] == I::MAX {
+                    container.index[id] = I::from_usize(container.ids.len());
+                    container.generations[id] = floor;
+                    container.ids.push(I::from_usize(id));
+                }
+            }
         }
+        Ok(container)
+    }
+
+    /// Builds a container where each element gets the id the caller chose, at generation 0, for interop with an
+    /// external id scheme. Returns the keys in the order given. Ids missing from `pairs` become free ids at
+    /// generation 1, so no key made up from the external ids resolves to a later element. Like `from_keyed_pairs`,
+    /// the metadata grows to the largest id given
+    pub fn from_indexed(
+        pairs: impl IntoIterator<Item = (usize, T)>,
+    ) -> Result<(Self, alloc::vec::Vec<SIVKey<I>>), IdError> {
+        let pairs = pairs
+            .into_iter()
+            .map(|(id, el)| {
+                if id >= I::MAX.to_usize() {
+                    return Err(IdError { id });
+                }
+                Ok((SIVKey::new(I::from_usize(id), I::default()), el))
+            })
+            .collect::<Result<alloc::vec::Vec<_>, _>>()?;
+        let keys = pairs.iter().map(|&(key, _)| key).collect();
+        let container = Self::from_keyed_pairs(pairs).map_err(|err| IdError { id: err.key.id.to_usize() })?;
+        Ok((container, keys))
+    }
+}
+
+impl_stable_core!(impl [T, I: IndexInt, A: Allocator] StableIndexVec<T, I, A>, after_remove = auto_compact);
+
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Builds the key of the live element at `data_index`
+    fn key_at(&self, data_index: usize) -> SIVKey<I> {
+        let id = self.ids[data_index];
+        SIVKey::new(id, self.generations[id.to_usize()])
     }
 
-    /// Gets the length of the data vector
-    pub fn len(&self) -> usize {
+    /// Gets the number of live elements. Same as `len`, named to pair with `allocated_slots`
+    pub fn live_count(&self) -> usize {
         self.data.len()
     }
 
-    /// Gets an optional reference to an element by its key
-    pub fn get(&self, key: SIVKey) -> Option<&T> {
-        let data_index = self.data_index(key)?;
-        self.data.get(data_index)
+    /// Gets the number of ids with allocated metadata, live or free. Same as `slot_count`
+    pub fn allocated_slots(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Gets `live_count() / allocated_slots()`, or 1.0 when nothing is allocated. A low value means most of
+    /// the metadata belongs to free ids, which `compact` can release
+    pub fn load_factor(&self) -> f64 {
+        if self.index.is_empty() {
+            return 1.0;
+        }
+        self.data.len() as f64 / self.index.len() as f64
     }
 
+    /// Gets the fraction of allocated ids that aren't holding a live element, or 0.0 when nothing is allocated
+    pub fn fragmentation(&self) -> f64 {
+        1.0 - self.load_factor()
+    }
 
-    /// Checks if the given element exists in the container
-    pub fn contains(&self, el: &T) -> bool {
-        self.data.contains(el)
+    /// Makes `remove` call `compact` whenever `fragmentation()` exceeds `threshold` afterwards. This moves keys, so
+    /// the old-to-new pairs are kept until `take_compaction_remap` collects them. Pass `None` to turn it back off
+    pub fn set_auto_compact(&mut self, threshold: Option<f64>) {
+        self.auto_compact_threshold = threshold;
     }
 
-    /// Adds an element to the container and returns its key
-    pub fn add(&mut self, el: T) -> SIVKey {
-        let index_len = self.index.len();
-        let data_len = self.data.len();
-        assert!(data_len <= index_len, "data.len() cannot be greater than index.len()");
+    /// Takes the old-to-new key pairs for every element moved by automatic compactions since the last call.
+    /// Old keys are the ones handed out before the first of those compactions, or by `add` in between them
+    pub fn take_compaction_remap(&mut self) -> alloc::vec::Vec<(SIVKey<I>, SIVKey<I>)> {
+        core::mem::take(&mut self.compaction_remap)
+    }
 
-        if data_len == index_len {
-            self.index.push(data_len);
-            self.generations.push(0);
-            self.ids.push(index_len);
-        } else {
-            self.generations[data_len] += 1;
+    /// Compacts the container if automatic compaction is enabled and the threshold has been exceeded
+    fn auto_compact(&mut self) {
+        if !self.auto_compact_threshold.is_some_and(|threshold| self.fragmentation() > threshold) {
+            return;
         }
 
-        self.data.push(el);
+        // pending pairs point at keys this compaction is about to move. compact's mapping is in data order, so
+        // remember where each pending pair's element sits and follow it to the element's new key afterwards
+        let mut pending = core::mem::take(&mut self.compaction_remap);
+        let pending_positions: alloc::vec::Vec<_> = pending.iter().map(|&(_, new_key)| self.data_index(new_key)).collect();
+        let mapping: alloc::vec::Vec<_> = self.compact().collect();
+        let mut positions = pending_positions.into_iter();
+        pending.retain_mut(|(_, new_key)| match positions.next().flatten() {
+            Some(data_index) => {
+                *new_key = mapping[data_index].1;
+                true
+            }
+            None => false,
+        });
+        pending.extend(mapping.into_iter().filter(|(old_key, new_key)| old_key != new_key));
+        self.compaction_remap = pending;
+    }
+
+    /// Gets the number of elements the container can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Reserves room for at least `additional` more elements. Free ids are reused first,
+    /// so the metadata vectors only reserve for the ids that would have to be newly allocated
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        let new_ids = (self.data.len() + additional).saturating_sub(self.ids.len());
+        self.index.reserve(new_ids);
+        self.generations.reserve(new_ids);
+        self.ids.reserve(new_ids);
+    }
+
+    /// Reserves room in the metadata vectors for at least `additional` newly allocated ids, without touching
+    /// the value storage. Useful ahead of heavy churn, where ids can outnumber the values alive at any one time
+    pub fn reserve_ids(&mut self, additional: usize) {
+        self.index.reserve(additional);
+        self.generations.reserve(additional);
+        self.ids.reserve(additional);
+    }
+
+    /// Shrinks the metadata vectors' capacity to fit the ids they hold, keeping the value storage's capacity for reuse
+    pub fn shrink_metadata(&mut self) {
+        self.index.shrink_to_fit();
+        self.generations.shrink_to_fit();
+        self.ids.shrink_to_fit();
+    }
+
+    /// Shrinks every backing vector's capacity toward its length, but not below `min_capacity`, like `Vec::shrink_to`.
+    /// The metadata never shrinks below the number of ids ever allocated, since free ids keep their generations
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity);
+        self.index.shrink_to(min_capacity);
+        self.generations.shrink_to(min_capacity);
+        self.ids.shrink_to(min_capacity);
+    }
+
+    /// Estimates the heap bytes held by the container's backing vectors, based on their capacity.
+    /// This doesn't include any heap memory owned by the values themselves
+    pub fn memory_usage(&self) -> usize {
+        let metadata_capacity = self.index.capacity() + self.generations.capacity() + self.ids.capacity();
+        self.data.capacity() * core::mem::size_of::<T>() + metadata_capacity * core::mem::size_of::<I>()
+    }
+
+    /// Gets the allocator backing the container
+    pub fn allocator(&self) -> &A {
+        self.data.allocator()
+    }
+
+    /// Renumbers the live elements' ids to be dense (`0..len()`) and releases the metadata of every free id,
+    /// returning a mapping from each element's old key to its new key so external references can be fixed up.
+    /// Every old key stops resolving. Retired ids stay retired and are skipped, so the ids are only dense around
+    /// them. If a live id has used up its generations, ids can't be renumbered safely and each key is mapped to
+    /// itself instead
+    pub fn compact(&mut self) -> impl Iterator<Item = (SIVKey<I>, SIVKey<I>)> + use<T, I, A> {
+        let len = self.data.len();
+        let old_keys = (0..len).map(|data_index| self.key_at(data_index));
+        // retired ids can never be handed out again, so they don't need a generation past theirs
+        let newest = (0..self.index.len())
+            .filter(|&id| self.index[id] != I::MAX)
+            .map(|id| self.generations[id])
+            .max();
+        let Some(generation) = newest.unwrap_or_default().checked_increment() else {
+            return old_keys.map(|key| (key, key)).collect::<alloc::vec::Vec<_>>().into_iter();
+        };
 
-        SIVKey {
-            id: self.ids[data_len],
-            generation: self.generations[data_len]
+        let retired: alloc::vec::Vec<usize> = (0..self.index.len()).filter(|&id| self.index[id] == I::MAX).collect();
+        let mut retired_ids = retired.iter().copied().peekable();
+        let mut next_id = 0;
+        let mut new_ids = alloc::vec::Vec::with_capacity(len);
+        while new_ids.len() < len {
+            if retired_ids.next_if_eq(&next_id).is_none() {
+                new_ids.push(next_id);
+            }
+            next_id += 1;
+        }
+        let slot_count = next_id.max(retired.last().map_or(0, |&id| id + 1));
+
+        let mapping: alloc::vec::Vec<_> = old_keys
+            .zip(&new_ids)
+            .map(|(old_key, &id)| (old_key, SIVKey::new(I::from_usize(id), generation)))
+            .collect();
+
+        self.generation_floor = generation;
+        self.ids.clear();
+        self.index.clear();
+        self.generations.clear();
+        self.index.resize(slot_count, I::MAX);
+        self.generations.resize(slot_count, generation);
+        for &id in &retired {
+            self.generations[id] = I::MAX;
+        }
+        for (data_index, &id) in new_ids.iter().enumerate() {
+            self.ids.push(I::from_usize(id));
+            self.index[id] = I::from_usize(data_index);
+        }
+        // ids below the last retired one that no element took become free
+        for id in 0..slot_count {
+            if self.index[id] == I::MAX && self.generations[id] != I::MAX {
+                self.index[id] = I::from_usize(self.ids.len());
+                self.ids.push(I::from_usize(id));
+            }
         }
+        self.shrink_metadata();
+
+        mapping.into_iter()
+    }
+
+    /// Renumbers the live elements' ids like `compact`, but returns the full old-to-new key translation as a map
+    /// for patching external structures. The new keys share one generation that is newer than any old key's
+    /// (rather than 0) so that no old key can ever resolve again
+    #[cfg(feature = "std")]
+    pub fn reindex(&mut self) -> std::collections::HashMap<SIVKey<I>, SIVKey<I>> {
+        self.compact().collect()
     }
 
-    /// Removes an element from the container by its key
-    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+    /// Gives the key's element a new key, leaving the value where it is, so every outstanding copy of the old key
+    /// stops resolving. Returns `None` if the key is already stale. If the id's generations are used up, the id is
+    /// retired and the value moves to a new id; that panics like `add` when no ids are left
+    pub fn regenerate(&mut self, key: SIVKey<I>) -> Option<SIVKey<I>> {
         let data_index = self.data_index(key)?;
-        self.data.get(data_index)?;
+        let id = key.id.to_usize();
+        if let Some(generation) = self.generations[id].checked_increment() {
+            self.generations[id] = generation;
+            return Some(SIVKey::new(key.id, generation));
+        }
+
+        assert!(self.index.len() < I::MAX.to_usize(), "StableIndexVec ran out of ids");
+        self.index.reserve(1);
+        self.generations.reserve(1);
+        let new_id = I::from_usize(self.index.len());
+        self.index.push(I::from_usize(data_index));
+        self.generations.push(self.generation_floor);
+        self.index[id] = I::MAX;
+        self.ids[data_index] = new_id;
+        Some(SIVKey::new(new_id, self.generation_floor))
+    }
+
+    /// Rotates a handle: makes `key` stale and returns the fresh key its value now lives under, or `None` if
+    /// `key` was already stale. This is `regenerate` under the name security-minded callers look for
+    pub fn invalidate(&mut self, key: SIVKey<I>) -> Option<SIVKey<I>> {
+        self.regenerate(key)
+    }
+
+    /// Moves every id (live, free or retired) to `f(id)`, keeping its generation, so the container's keys line up
+    /// with an external id space. Ids that nothing maps to become free ids. Fails without changing anything if `f`
+    /// sends two ids to the same one
+    pub fn remap_ids<F: FnMut(usize) -> usize>(&mut self, f: F) -> Result<(), RemapError> {
+        let new_ids: alloc::vec::Vec<usize> = (0..self.index.len()).map(f).collect();
+        if let Some(&id) = new_ids.iter().find(|&&id| id >= I::MAX.to_usize()) {
+            return Err(RemapError { id });
+        }
+        let slot_count = new_ids.iter().max().map_or(0, |&id| id + 1);
+        let mut taken = alloc::vec![false; slot_count];
+        for &id in &new_ids {
+            if core::mem::replace(&mut taken[id], true) {
+                return Err(RemapError { id });
+            }
+        }
+
+        let old_index: alloc::vec::Vec<I> = self.index.drain(..).collect();
+        let old_generations: alloc::vec::Vec<I> = self.generations.drain(..).collect();
+        self.index.resize(slot_count, I::MAX);
+        self.generations.resize(slot_count, self.generation_floor);
+        for (old_id, &new_id) in new_ids.iter().enumerate() {
+            self.index[new_id] = old_index[old_id];
+            self.generations[new_id] = old_generations[old_id];
+        }
 
-        let last_index = self.data.len() - 1;
-        if data_index < last_index {
-            self.data.swap(data_index, last_index);
-            self.generations.swap(data_index, last_index);
-            self.ids.swap(data_index, last_index);
-            self.index[self.ids[data_index]] = data_index;
-            self.index[self.ids[last_index]] = last_index;
+        // positions in ids don't change, so index still points at the right place for every moved id
+        for id in self.ids.iter_mut() {
+            *id = I::from_usize(new_ids[id.to_usize()]);
+        }
+        for (_, new_key) in self.compaction_remap.iter_mut() {
+            new_key.id = I::from_usize(new_ids[new_key.id.to_usize()]);
         }
+        for (id, _) in taken.iter().enumerate().filter(|&(_, &taken)| !taken) {
+            self.index[id] = I::from_usize(self.ids.len());
+            self.ids.push(I::from_usize(id));
+        }
+        Ok(())
+    }
+
+    /// Gets the key of the element at `data_index` in the dense buffer returned by `as_slice`,
+    /// or `None` if the position is out of range
+    pub fn key_for(&self, data_index: usize) -> Option<SIVKey<I>> {
+        (data_index < self.data.len()).then(|| self.key_at(data_index))
+    }
+
+    /// Gets the element at the start of the data buffer. Removals reorder the buffer,
+    /// so this is not necessarily the oldest element (see `OrderedStableIndexVec` for that)
+    pub fn first(&self) -> Option<(SIVKey<I>, &T)> {
+        let el = self.data.first()?;
+        Some((self.key_at(0), el))
+    }
+
+    /// Gets the element at the end of the data buffer, position `len() - 1`. Removals reorder the buffer,
+    /// so this is not necessarily the newest element (see `OrderedStableIndexVec` for that)
+    pub fn last(&self) -> Option<(SIVKey<I>, &T)> {
+        let el = self.data.last()?;
+        Some((self.key_at(self.data.len() - 1), el))
+    }
+
+    /// Counts the values that satisfy the predicate, scanning the dense value buffer directly
+    pub fn count<F: FnMut(&T) -> bool>(&self, mut f: F) -> usize {
+        self.data.iter().filter(|el| f(el)).count()
+    }
+
+    /// Returns an iterator over each element's key, position in the dense buffer, and value.
+    /// The positions are only meaningful until the next mutation, since removals move elements around
+    pub fn iter_with_index(&self) -> impl Iterator<Item = (SIVKey<I>, usize, &T)> + '_ {
+        self.iter().enumerate().map(|(data_index, (key, el))| (key, data_index, el))
+    }
+
+    /// Returns an iterator over the raw id, generation and value of each live element, in data order.
+    /// Meant for debugging and for mirroring the container's identity state outside of `SIVKey`
+    pub fn raw_entries(&self) -> impl Iterator<Item = (usize, usize, &T)> + '_ {
+        self.ids.iter().zip(&self.data).map(|(&id, el)| (id.to_usize(), self.generations[id.to_usize()].to_usize(), el))
+    }
+
+    /// Returns an iterator over every key live in both containers, with the value from each, in this container's
+    /// data order. Meant for parallel stores that share a key space, such as component arrays indexed by entity
+    pub fn zip<'a, U, B: Allocator>(
+        &'a self,
+        other: &'a StableIndexVec<U, I, B>,
+    ) -> impl Iterator<Item = (SIVKey<I>, &'a T, &'a U)> + 'a {
+        self.iter().filter_map(move |(key, el)| Some((key, el, other.get(key)?)))
+    }
+
+    /// Returns an iterator over the keys live in both containers. Like the other key set operations, this only
+    /// means something when both containers share a key space (for example, one was cloned or rebuilt from the other)
+    pub fn key_intersection<'a, U, B: Allocator>(
+        &'a self,
+        other: &'a StableIndexVec<U, I, B>,
+    ) -> impl Iterator<Item = SIVKey<I>> + 'a {
+        self.iter().map(|(key, _)| key).filter(move |&key| other.contains_key(key))
+    }
+
+    /// Returns an iterator over the keys live in either container, each yielded once
+    pub fn key_union<'a, U, B: Allocator>(
+        &'a self,
+        other: &'a StableIndexVec<U, I, B>,
+    ) -> impl Iterator<Item = SIVKey<I>> + 'a {
+        self.iter().map(|(key, _)| key).chain(other.key_difference(self))
+    }
+
+    /// Returns an iterator over the keys live in this container but not in `other`
+    pub fn key_difference<'a, U, B: Allocator>(
+        &'a self,
+        other: &'a StableIndexVec<U, I, B>,
+    ) -> impl Iterator<Item = SIVKey<I>> + 'a {
+        self.iter().map(|(key, _)| key).filter(move |&key| !other.contains_key(key))
+    }
+
+    /// Records the keys that are live right now, so they can be checked with `contains_key` later
+    pub fn snapshot_keys(&self) -> alloc::vec::Vec<SIVKey<I>> {
+        (0..self.data.len()).map(|data_index| self.key_at(data_index)).collect()
+    }
+
+    /// Returns the live keys in ascending order (by id, then generation), for reproducible output.
+    /// Unlike `keys`, this doesn't depend on removal order, but it collects and sorts so it costs O(n log n)
+    pub fn keys_sorted(&self) -> impl Iterator<Item = SIVKey<I>> + use<T, I, A> {
+        let mut keys = self.snapshot_keys();
+        keys.sort_unstable();
+        keys.into_iter()
+    }
+
+    /// Collects every live entry sorted ascending by key, for snapshots and tests that shouldn't depend on
+    /// removal order
+    pub fn to_vec_sorted_by_key(&self) -> alloc::vec::Vec<(SIVKey<I>, &T)> {
+        let mut entries: alloc::vec::Vec<_> = self.iter().collect();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        entries
+    }
+
+    /// Returns an iterator over all key-value pairs in ascending key order. Unlike `iter`, the order doesn't depend
+    /// on removals, but setting it up collects and sorts the entries so it costs O(n log n)
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (SIVKey<I>, &T)> + '_ {
+        self.to_vec_sorted_by_key().into_iter()
+    }
 
-        self.data.pop()
+    /// Consumes the container, returning an iterator over its keys in data order
+    pub fn into_keys(self) -> impl Iterator<Item = SIVKey<I>> {
+        self.into_iter().map(|(key, _)| key)
+    }
+
+    /// Consumes the container, returning an iterator over its values in data order
+    pub fn into_values(self) -> impl Iterator<Item = T> {
+        self.into_iter().map(|(_, el)| el)
+    }
+
+    /// Consumes the container, keeping only its values as a boxed slice in internal (unstable) order and
+    /// freeing all key metadata. The box uses the container's allocator; it's `allocator_api2`'s `Box`, which is
+    /// the std `Box` when the `nightly` feature is on
+    pub fn into_boxed_slice(self) -> allocator_api2::boxed::Box<[T], A> {
+        self.data.into_boxed_slice()
+    }
+
+    /// Gets all values as one contiguous slice, in internal order.
+    /// The order is unstable across removals, and positions in the slice are not keys
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Gets the dense buffer of live values, which always holds exactly `len()` values with no holes.
+    /// This is the same buffer as `as_slice`, and its order is unstable across removals
+    pub fn values_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Gets all values as one contiguous mutable slice, in internal order.
+    /// The order is unstable across removals, and positions in the slice are not keys
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// Gets a raw pointer to the contiguous value buffer, for handing it to FFI or GPU upload code.
+    /// Only the first `len()` values are initialized, and the buffer moves whenever the container reallocates
+    pub fn as_ptr(&self) -> *const T {
+        self.data.as_ptr()
+    }
+
+    /// Gets a raw mutable pointer to the contiguous value buffer.
+    /// Only the first `len()` values are initialized, and the buffer moves whenever the container reallocates
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.data.as_mut_ptr()
     }
 
     /// Internal debugging method that shows all internal vectors
     /// This is not public and is only used for testing and development
     #[cfg(test)]
     #[allow(dead_code)]
-    pub(crate) fn debug_internals(&self) -> String
+    pub(crate) fn debug_internals(&self) -> alloc::string::String
     where
-        T: std::fmt::Debug,
+        T: core::fmt::Debug,
     {
-        format!(
+        alloc::format!(
             "StableIndexVec {{\n  index: {:?},\n generations: {:?},\n  ids: {:?},\n  data: {:?}\n}}",
             self.index, self.generations, self.ids, self.data
         )
     }
 
-    /// Returns an iterator over all key-value pairs in the container. The iterator element type is (SIVKey, &'a T)
-    pub fn iter(&self) -> Iter<'_, T> {
-        Iter {
-            container: self,
-            position: 0,
+    /// Test-only setter used to force an id's generation close to overflow
+    #[cfg(test)]
+    pub(crate) fn set_generation(&mut self, id: I, generation: I) {
+        self.generations[id.to_usize()] = generation;
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Gets the position of the key's element in the dense buffer returned by `as_slice`.
+    /// The position is only meaningful until the next mutation, since removals move elements around
+    pub fn data_index_of(&self, key: SIVKey<I>) -> Option<usize> {
+        self.data_index(key)
+    }
+
+    /// Looks up a batch of keys at once, returning `None` for each stale key. Duplicate keys are fine
+    pub fn get_many<const N: usize>(&self, keys: [SIVKey<I>; N]) -> [Option<&T>; N] {
+        keys.map(|key| self.get(key))
+    }
+
+    /// Gets mutable references to several elements at once. Returns `None` if any key is stale or two keys are equal
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [SIVKey<I>; N]) -> Option<[&mut T; N]> {
+        let mut indices = [0; N];
+        for (data_index, key) in indices.iter_mut().zip(keys) {
+            *data_index = self.data_index(key)?;
+        }
+        self.data.get_disjoint_mut(indices).ok()
+    }
+
+    /// Gets mutable references to two elements at once. Returns `None` if either key is stale or they are equal
+    pub fn get2_mut(&mut self, a: SIVKey<I>, b: SIVKey<I>) -> Option<(&mut T, &mut T)> {
+        let [a, b] = self.get_disjoint_mut([a, b])?;
+        Some((a, b))
+    }
+
+    /// Removes the element at the end of the data buffer and returns it with its key, which is stale afterwards
+    pub fn pop(&mut self) -> Option<(SIVKey<I>, T)> {
+        let (key, _) = self.last()?;
+        let el = self.remove(key)?;
+        Some((key, el))
+    }
+
+    /// Moves every element of `other` into this container under fresh keys, returning a mapping from
+    /// each element's key in `other` to its new key. Keys already in this container are untouched
+    pub fn append(&mut self, other: Self) -> alloc::vec::Vec<(SIVKey<I>, SIVKey<I>)> {
+        self.reserve(other.len());
+        let old_keys = other.snapshot_keys();
+        old_keys.into_iter().zip(other.data).map(|(old_key, el)| (old_key, self.add(el))).collect()
+    }
+
+    /// Adds a clone of every value in `items`, returning the key assigned to each, in order
+    pub fn extend_from_slice(&mut self, items: &[T]) -> alloc::vec::Vec<SIVKey<I>>
+    where
+        T: Clone,
+    {
+        self.reserve(items.len());
+        items.iter().map(|el| self.add(el.clone())).collect()
+    }
+
+    /// Adds an element only if the container holds fewer than `limit` elements, otherwise hands the value back.
+    /// For a limit that's part of the container's type, see `BoundedStableIndexVec`
+    pub fn try_add(&mut self, el: T, limit: usize) -> Result<SIVKey<I>, T> {
+        if self.data.len() >= limit {
+            return Err(el);
+        }
+        Ok(self.add(el))
+    }
+
+    /// Gets the element under `key` if it's live, otherwise adds the value `f` returns. Returns the key the element
+    /// lives under, which is `key` itself on a hit and a new key on a miss, with a mutable reference to it
+    pub fn get_or_insert_with(&mut self, key: SIVKey<I>, f: impl FnOnce() -> T) -> (SIVKey<I>, &mut T) {
+        let (key, data_index) = match self.data_index(key) {
+            Some(data_index) => (key, data_index),
+            None => (self.add(f()), self.data.len() - 1),
+        };
+        (key, &mut self.data[data_index])
+    }
+
+    /// Adds an element like `add`, also reporting whether it reused a freed id (`true`) or had to grow the metadata
+    pub fn add_tracked(&mut self, el: T) -> (SIVKey<I>, bool) {
+        let reused = self.data.len() < self.ids.len();
+        (self.add(el), reused)
+    }
+
+    /// Adds every item and returns their keys in order, reserving room from the iterator's size hint first
+    pub fn add_many<It: IntoIterator<Item = T>>(&mut self, items: It) -> alloc::vec::Vec<SIVKey<I>> {
+        let items = items.into_iter();
+        self.reserve(items.size_hint().0);
+        items.map(|el| self.add(el)).collect()
+    }
+
+    /// Keeps only the elements the predicate returns `true` for, returning how many were removed.
+    /// Removed elements' keys go stale; kept elements keep their keys until automatic compaction, which runs
+    /// at most once, after the whole pass
+    pub fn retain<F: FnMut(SIVKey<I>, &T) -> bool>(&mut self, mut f: F) -> usize {
+        let len = self.data.len();
+        let mut data_index = 0;
+        while data_index < self.data.len() {
+            let key = self.key_at(data_index);
+            if f(key, &self.data[data_index]) {
+                data_index += 1;
+            } else {
+                // the last element is swapped into this slot, so check the same position again
+                self.swap_remove_key(key);
+            }
+        }
+        self.auto_compact();
+        len - self.data.len()
+    }
+
+    /// Keeps only the elements whose key the predicate returns `true` for, returning how many were removed.
+    /// Handy for culling against a key set tracked elsewhere
+    pub fn retain_keys<F: FnMut(SIVKey<I>) -> bool>(&mut self, mut f: F) -> usize {
+        self.retain(|key, _| f(key))
+    }
+
+    /// Removes every element the predicate returns `true` for and returns them with their (now stale) keys.
+    /// Each element is tested exactly once, even though removals swap later elements into the tested positions.
+    /// Automatic compaction runs at most once, after the whole pass, so the returned keys are the ones callers held
+    pub fn remove_where<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> alloc::vec::Vec<(SIVKey<I>, T)> {
+        let mut removed = alloc::vec::Vec::new();
+        let mut data_index = 0;
+        while data_index < self.data.len() {
+            if f(&self.data[data_index]) {
+                let key = self.key_at(data_index);
+                if let Some(el) = self.swap_remove_key(key) {
+                    removed.push((key, el));
+                }
+            } else {
+                data_index += 1;
+            }
+        }
+        self.auto_compact();
+        removed
+    }
+
+    /// Removes every element, returning an iterator over each one with the key it had. All of those keys are stale
+    /// afterwards, even if the iterator is dropped before it is exhausted (the remaining elements are dropped with it)
+    pub fn drain(&mut self) -> impl Iterator<Item = (SIVKey<I>, T)> + '_ {
+        let keys = self.snapshot_keys();
+        for slot in (0..self.data.len()).rev() {
+            self.release_id(self.ids[slot], slot);
+        }
+        keys.into_iter().zip(self.data.drain(..))
+    }
+
+    /// Runs `f` on the container, undoing all of its changes if it returns `Err` (or, with the `std` feature,
+    /// panics). This clones the whole container up front, so it costs O(n) time and memory even when `f` succeeds
+    pub fn transaction<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E>
+    where
+        T: Clone,
+        A: Clone,
+    {
+        let snapshot = self.clone();
+        #[cfg(feature = "std")]
+        let result = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(self))) {
+            Ok(result) => result,
+            Err(panic) => {
+                *self = snapshot;
+                std::panic::resume_unwind(panic);
+            }
+        };
+        #[cfg(not(feature = "std"))]
+        let result = f(self);
+
+        if result.is_err() {
+            *self = snapshot;
+        }
+        result
+    }
+
+    /// Creates a new container holding clones of the elements that match the predicate, under their original keys.
+    /// Keys of elements that didn't match are invalid in the new container
+    pub fn filter<F: FnMut(SIVKey<I>, &T) -> bool>(&self, mut f: F) -> Self
+    where
+        T: Clone,
+        A: Clone,
+    {
+        let mut filtered = Self {
+            index: self.index.clone(),
+            generations: self.generations.clone(),
+            ids: self.ids.clone(),
+            data: Vec::new_in(self.data.allocator().clone()),
+            generation_floor: self.generation_floor,
+            auto_compact_threshold: self.auto_compact_threshold,
+            compaction_remap: alloc::vec::Vec::new(),
+        };
+
+        for (data_index, el) in self.data.iter().enumerate() {
+            if f(self.key_at(data_index), el) {
+                let slot = filtered.data.len();
+                filtered.ids.swap(slot, data_index);
+                filtered.index[filtered.ids[slot].to_usize()] = I::from_usize(slot);
+                filtered.index[filtered.ids[data_index].to_usize()] = I::from_usize(data_index);
+                filtered.data.push(el.clone());
+            }
+        }
+
+        // the ids that didn't match are free now, so bump them to keep their keys from being reissued
+        for slot in (filtered.data.len()..self.data.len()).rev() {
+            filtered.release_id(filtered.ids[slot], slot);
+        }
+        filtered
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> where T: PartialEq {
+    /// Gets the key of the first element (in data order) equal to `value`. This is O(n)
+    pub fn key_of(&self, value: &T) -> Option<SIVKey<I>> {
+        let data_index = self.data.iter().position(|el| el == value)?;
+        Some(self.key_at(data_index))
+    }
+
+    /// Gets the key of a value equal to `value` if there is one, otherwise adds `value` and returns its new key.
+    /// The lookup is O(n), like `key_of`
+    pub fn get_or_add(&mut self, value: T) -> SIVKey<I> {
+        match self.key_of(&value) {
+            Some(key) => key,
+            None => self.add(value),
         }
     }
 
-    /// Returns an iterator over the valid keys in the container
-    pub fn keys(&self) -> impl Iterator<Item = SIVKey> + '_ {
-        self.iter().map(|(key, _)| key)
+    /// Checks whether both containers hold the same values the same number of times, ignoring keys and order.
+    /// Unlike `==`, this matches containers built independently. This is O(n²) since `T` is only `PartialEq`
+    pub fn equals_ignoring_keys(&self, other: &Self) -> bool {
+        if self.data.len() != other.data.len() {
+            return false;
+        }
+        let mut matched = alloc::vec![false; other.data.len()];
+        self.data.iter().all(|el| {
+            let found = (0..other.data.len()).find(|&i| !matched[i] && other.data[i] == *el);
+            found.map(|i| matched[i] = true).is_some()
+        })
+    }
+
+    /// Removes values equal to an earlier value in data order, returning how many were removed.
+    /// The first occurrence of each value keeps its key. This is O(n²) since `T` is only `PartialEq`
+    pub fn dedup(&mut self) -> usize {
+        let duplicates: alloc::vec::Vec<_> = (0..self.data.len())
+            .filter(|&data_index| self.data[..data_index].contains(&self.data[data_index]))
+            .map(|data_index| self.key_at(data_index))
+            .collect();
+        // compacting part way through would make the rest of the collected keys stale
+        for &key in &duplicates {
+            self.swap_remove_key(key);
+        }
+        self.auto_compact();
+        duplicates.len()
     }
+}
 
-    /// Returns an iterator over the values in the container
-    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
-        self.iter().map(|(_, value)| value)
+impl<T, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> where T: Default {
+    /// Gets the element under `key` if it's live, otherwise adds `T::default()` and returns its new key.
+    /// See `get_or_insert_with`
+    pub fn get_or_insert_default(&mut self, key: SIVKey<I>) -> (SIVKey<I>, &mut T) {
+        self.get_or_insert_with(key, T::default)
     }
 }
 
 /// Iterator over keys and references to elements in a StableIndexVec
-pub struct Iter<'a, T> {
-    container: &'a StableIndexVec<T>,
+pub struct Iter<'a, T, I: IndexInt = usize> {
+    ids: &'a [I],
+    generations: &'a [I],
+    data: &'a [T],
     position: usize,
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
-    type Item = (SIVKey, &'a T);
+impl<'a, T, I: IndexInt> Iterator for Iter<'a, T, I> {
+    type Item = (SIVKey<I>, &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.position >= self.container.data.len() {
+        if self.position >= self.data.len() {
             return None;
         }
 
         let current_pos = self.position;
         self.position += 1;
 
-        let id = self.container.ids[current_pos];
+        let id = self.ids[current_pos];
         let key = SIVKey {
             id,
-            generation: self.container.generations[id],
+            generation: self.generations[id.to_usize()],
         };
-        Some((key, &self.container.data[current_pos]))
+        Some((key, &self.data[current_pos]))
+    }
+}
+
+/// Consuming iterator over the keys and elements of a StableIndexVec, in data order
+pub struct IntoIter<T, I: IndexInt = usize, A: Allocator = Global> {
+    ids: Vec<I, A>,
+    generations: Vec<I, A>,
+    data: allocator_api2::vec::IntoIter<T, A>,
+    position: usize,
+}
+
+impl<T, I: IndexInt, A: Allocator> Iterator for IntoIter<T, I, A> {
+    type Item = (SIVKey<I>, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let el = self.data.next()?;
+        let id = self.ids[self.position];
+        self.position += 1;
+        Some((SIVKey::new(id, self.generations[id.to_usize()]), el))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.data.size_hint()
+    }
+}
+
+impl<T, I: IndexInt, A: Allocator> ExactSizeIterator for IntoIter<T, I, A> {}
+
+impl<T, I: IndexInt, A: Allocator> IntoIterator for StableIndexVec<T, I, A> {
+    type Item = (SIVKey<I>, T);
+    type IntoIter = IntoIter<T, I, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            ids: self.ids,
+            generations: self.generations,
+            data: self.data.into_iter(),
+            position: 0,
+        }
     }
 }
 
@@ -526,4 +1453,1760 @@ mod tests {
             assert!(container.contains(value));
         }
     }
+
+    #[test]
+    fn exhausted_generation_retires_id() {
+        let mut container = StableIndexVec::<isize>::new();
+        let key1 = container.add(1);
+        container.remove(key1);
+
+        container.set_generation(key1.id, usize::MAX);
+        let key2 = container.add(2);
+        assert_eq!(key2, SIVKey::new(key1.id, usize::MAX));
+        container.remove(key2);
+
+        // the generation can't be advanced again, so the id must not be reused
+        let key3 = container.add(3);
+        assert_ne!(key3.id, key1.id);
+
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key3), Some(&3));
+        for generation in [0, 1, usize::MAX - 1, usize::MAX] {
+            assert_eq!(container.get(SIVKey::new(key1.id, generation)), None);
+        }
+
+        let key4 = container.add(4);
+        assert_ne!(key4.id, key1.id);
+        assert_eq!(container.get(key3), Some(&3));
+        assert_eq!(container.get(key4), Some(&4));
+    }
+
+    #[test]
+    fn retired_id_does_not_disturb_other_free_ids() {
+        let mut container = StableIndexVec::<isize>::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        container.remove(key2);
+
+        container.set_generation(key1.id, usize::MAX);
+        let key1 = SIVKey::new(key1.id, usize::MAX);
+        assert_eq!(container.remove(key1), Some(1));
+
+        let key3 = container.add(3);
+        assert_eq!(key3.id, key2.id);
+        assert_eq!(key3.generation, key2.generation + 1);
+
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key3), Some(&3));
+    }
+
+    #[test]
+    fn generations_follow_ids_across_swaps() {
+        let mut container = StableIndexVec::<isize>::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+        let key4 = container.add(4);
+
+        container.remove(key2);
+        let key5 = container.add(5);
+
+        assert_eq!(container.get(key1), Some(&1));
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key3), Some(&3));
+        assert_eq!(container.get(key4), Some(&4));
+        assert_eq!(container.get(key5), Some(&5));
+
+        container.remove(key1);
+        let key6 = container.add(6);
+
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key3), Some(&3));
+        assert_eq!(container.get(key4), Some(&4));
+        assert_eq!(container.get(key5), Some(&5));
+        assert_eq!(container.get(key6), Some(&6));
+
+        for (key, value) in container.iter() {
+            assert_eq!(container.get(key), Some(value));
+        }
+    }
+
+    /// Small deterministic xorshift generator so stress tests don't need an rng dependency
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn keys_never_collide_under_random_operations() {
+        let mut rng = XorShift(0x2545_F491_4F6C_DD1D);
+        let mut container = StableIndexVec::new();
+        let mut issued = std::collections::HashSet::new();
+        let mut live: Vec<(SIVKey, u64)> = Vec::new();
+        let mut removed: Vec<SIVKey> = Vec::new();
+
+        for step in 0..20_000 {
+            if live.is_empty() || rng.below(5) < 3 {
+                let value = rng.next();
+                let key = container.add(value);
+                assert!(issued.insert(key), "key {key:?} was issued twice (step {step})");
+                live.push((key, value));
+            } else {
+                let (key, value) = live.swap_remove(rng.below(live.len()));
+                assert_eq!(container.remove(key), Some(value));
+                removed.push(key);
+            }
+
+            if step % 1_000 == 0 {
+                assert_eq!(container.len(), live.len());
+                for (key, value) in &live {
+                    assert_eq!(container.get(*key), Some(value));
+                }
+                for key in &removed {
+                    assert_eq!(container.get(*key), None);
+                }
+                for (key, value) in container.iter() {
+                    assert_eq!(container.get(key), Some(value));
+                }
+                assert_eq!(container.validate(), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn remove_stable_preserves_insertion_order() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+        let key4 = container.add(4);
+        let key5 = container.add(5);
+
+        assert_eq!(container.remove_stable(key2), Some(2));
+        assert_eq!(container.remove_stable(key4), Some(4));
+
+        let values: Vec<_> = container.values().copied().collect();
+        assert_eq!(values, [1, 3, 5]);
+        let keys: Vec<_> = container.keys().collect();
+        assert_eq!(keys, [key1, key3, key5]);
+
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.get(key4), None);
+        assert_eq!(container.remove_stable(key2), None);
+
+        let key6 = container.add(6);
+        assert!(![key2, key4].contains(&key6));
+        let values: Vec<_> = container.values().copied().collect();
+        assert_eq!(values, [1, 3, 5, 6]);
+        for (key, value) in container.iter() {
+            assert_eq!(container.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn validate_accepts_consistent_containers() {
+        let mut container = StableIndexVec::new();
+        assert_eq!(container.validate(), Ok(()));
+
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        container.add(3);
+        container.remove(key1);
+        container.remove_stable(key2);
+        assert_eq!(container.validate(), Ok(()));
+
+        let key4 = container.add(4);
+        container.remove(key4);
+        container.set_generation(key4.id, usize::MAX);
+        let key5 = container.add(5);
+        container.remove(key5);
+        assert_eq!(container.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_broken_invariants() {
+        let mut container = StableIndexVec::new();
+        container.add(1);
+        container.add(2);
+
+        let mut broken = container.clone();
+        broken.data.push(3);
+        assert!(broken.validate().unwrap_err().contains("data.len()"));
+
+        let mut broken = container.clone();
+        broken.generations.pop();
+        assert!(broken.validate().unwrap_err().contains("generations.len()"));
+
+        let mut broken = container.clone();
+        broken.ids[1] = 0;
+        assert!(broken.validate().unwrap_err().contains("more than once"));
+
+        let mut broken = container.clone();
+        broken.ids[1] = 7;
+        assert!(broken.validate().unwrap_err().contains("out of range"));
+
+        let mut broken = container.clone();
+        broken.index.swap(0, 1);
+        assert!(broken.validate().unwrap_err().contains("does not point back"));
+    }
+
+    #[test]
+    fn eq_ignores_internal_order() {
+        let mut container1 = StableIndexVec::new();
+        let key1 = container1.add(1);
+        container1.add(2);
+        container1.add(3);
+
+        let mut container2 = container1.clone();
+
+        container1.remove(key1);
+        container2.remove_stable(key1);
+
+        assert_ne!(container1.values().collect::<Vec<_>>(), container2.values().collect::<Vec<_>>());
+        assert!(container1 == container2);
+    }
+
+    #[test]
+    fn eq_compares_keys_and_values() {
+        let mut container1 = StableIndexVec::new();
+        let key1 = container1.add(1);
+        container1.add(2);
+
+        let mut container2 = container1.clone();
+        assert!(container1 == container2);
+
+        // same values but a different key
+        container2.remove(key1);
+        container2.add(1);
+        assert!(container1 != container2);
+
+        let mut container3 = StableIndexVec::new();
+        container3.add(1);
+        container3.add(5);
+        assert!(container1 != container3);
+
+        container3.add(6);
+        assert!(container1 != container3);
+        assert!(StableIndexVec::<isize>::new() == StableIndexVec::new());
+    }
+
+    #[cfg(feature = "std")]
+    fn hash_of<T: std::hash::Hash>(value: &T) -> u64 {
+        use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher};
+        BuildHasherDefault::<DefaultHasher>::default().hash_one(value)
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn hash_ignores_internal_order() {
+        let mut container1 = StableIndexVec::new();
+        let key1 = container1.add(1);
+        container1.add(2);
+        container1.add(3);
+
+        let mut container2 = container1.clone();
+        container1.remove(key1);
+        container2.remove_stable(key1);
+
+        assert_eq!(container1, container2);
+        assert_eq!(hash_of(&container1), hash_of(&container2));
+
+        container2.add(4);
+        assert_ne!(hash_of(&container1), hash_of(&container2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn containers_work_in_hash_sets() {
+        let mut container1 = StableIndexVec::new();
+        let key1 = container1.add("a");
+        container1.add("b");
+        let mut container2 = container1.clone();
+        container2.remove(key1);
+
+        let mut set = std::collections::HashSet::new();
+        assert!(set.insert(container1.clone()));
+        assert!(set.insert(container2));
+        assert!(!set.insert(container1));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn slot_count_stays_bounded_when_cycling() {
+        let mut container = StableIndexVec::new();
+        let keep1 = container.add(-1);
+        let keep2 = container.add(-2);
+
+        let mut last_key = None;
+        for i in 0..100_000 {
+            let key = container.add(i);
+            assert_eq!(container.remove(key), Some(i));
+            assert_ne!(Some(key), last_key);
+            last_key = Some(key);
+        }
+
+        assert_eq!(container.slot_count(), 3);
+        assert_eq!(container.get(keep1), Some(&-1));
+        assert_eq!(container.get(keep2), Some(&-2));
+        assert_eq!(container.get(last_key.unwrap()), None);
+    }
+
+    #[test]
+    fn smaller_index_types_work() {
+        let mut container = StableIndexVec::<&str, u32>::default();
+        let key1 = container.add("a");
+        let key2 = container.add("b");
+        container.remove(key1);
+        let key3 = container.add("c");
+
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.get(key2), Some(&"b"));
+        assert_eq!(container.get(key3), Some(&"c"));
+        assert_eq!(key3, SIVKey::new(0u32, 1));
+        assert_eq!(std::mem::size_of::<SIVKey<u32>>(), 8);
+        assert_eq!(container.validate(), Ok(()));
+    }
+
+    #[test]
+    fn small_generations_retire_ids_on_overflow() {
+        let mut container = StableIndexVec::<usize, u16>::default();
+        let first = container.add(0);
+        let other = container.add(1);
+        container.remove(first);
+
+        let mut keys = vec![first];
+        for i in 0..u16::MAX as usize {
+            let key = container.add(i);
+            assert_eq!(key.id, first.id);
+            container.remove(key);
+            keys.push(key);
+        }
+
+        let key = container.add(7);
+        assert_ne!(key.id, first.id);
+        assert!(keys.iter().all(|key| container.get(*key).is_none()));
+        assert_eq!(container.get(other), Some(&1));
+        assert_eq!(container.get(key), Some(&7));
+        assert_eq!(container.validate(), Ok(()));
+    }
+
+    /// Allocator that forwards to `Global` while counting the allocations it makes
+    #[derive(Clone, Default)]
+    struct CountingAllocator(std::rc::Rc<std::cell::Cell<usize>>);
+
+    unsafe impl Allocator for CountingAllocator {
+        fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+            self.0.set(self.0.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    /// Allocator that panics (standing in for an out-of-memory error) once its budget of allocations runs out
+    #[derive(Clone)]
+    struct FailingAllocator(std::rc::Rc<std::cell::Cell<usize>>);
+
+    unsafe impl Allocator for FailingAllocator {
+        fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+            let budget = self.0.get();
+            assert!(budget > 0, "simulated out of memory");
+            self.0.set(budget - 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn custom_allocator_backs_all_vectors() {
+        let alloc = CountingAllocator::default();
+        let mut container = StableIndexVec::<isize, usize, _>::new_in(alloc.clone());
+        assert_eq!(alloc.0.get(), 0);
+
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        assert_eq!(alloc.0.get(), 4);
+
+        container.remove(key1);
+        assert_eq!(container.get(key2), Some(&2));
+        assert_eq!(container.allocator().0.get(), 4);
+    }
+
+    #[test]
+    fn with_capacity_and_reserve_preallocate() {
+        let alloc = CountingAllocator::default();
+        let mut container = StableIndexVec::<isize, usize, _>::with_capacity_in(8, alloc.clone());
+        assert_eq!(alloc.0.get(), 4);
+        assert!(container.capacity() >= 8);
+
+        for i in 0..8 {
+            container.add(i);
+        }
+        assert_eq!(alloc.0.get(), 4);
+
+        container.reserve(100);
+        assert!(container.capacity() >= 108);
+        let allocations = alloc.0.get();
+        for i in 0..100 {
+            container.add(i);
+        }
+        assert_eq!(alloc.0.get(), allocations);
+
+        let container = StableIndexVec::<isize>::with_capacity(16);
+        assert!(container.capacity() >= 16);
+    }
+
+    #[test]
+    fn contains_key_tracks_liveness() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        assert!(container.contains_key(key1));
+        assert!(container.contains_key(key2));
+
+        container.remove(key1);
+        assert!(!container.contains_key(key1));
+        assert!(container.contains_key(key2));
+
+        let key3 = container.add(3);
+        assert!(!container.contains_key(key1));
+        assert!(container.contains_key(key3));
+        assert!(!container.contains_key(SIVKey::new(99, 0)));
+    }
+
+    #[test]
+    fn slices_cover_all_live_values() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+        container.remove(key1);
+
+        let mut values = container.as_slice().to_vec();
+        values.sort();
+        assert_eq!(values, [2, 3]);
+
+        for value in container.as_mut_slice() {
+            *value *= 10;
+        }
+        assert_eq!(container.get(key2), Some(&20));
+        assert_eq!(container.get(key3), Some(&30));
+    }
+
+    #[test]
+    fn raw_pointers_match_slices() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        container.add(2);
+        container.add(3);
+        container.remove(key1);
+
+        assert_eq!(container.as_ptr(), container.as_slice().as_ptr());
+        let values = unsafe { std::slice::from_raw_parts(container.as_ptr(), container.len()) };
+        assert_eq!(values, container.as_slice());
+
+        let ptr = container.as_mut_ptr();
+        unsafe { *ptr = 7 };
+        assert!(container.contains(&7));
+    }
+
+    #[test]
+    fn compact_renumbers_ids_densely() {
+        let mut container = StableIndexVec::new();
+        let keys: Vec<_> = (0..100).map(|i| container.add(i)).collect();
+        for key in keys.iter().skip(5) {
+            container.remove(*key);
+        }
+        assert_eq!(container.slot_count(), 100);
+
+        let mapping: Vec<_> = container.compact().collect();
+        assert_eq!(mapping.len(), 5);
+        assert_eq!(container.slot_count(), 5);
+        assert_eq!(container.validate(), Ok(()));
+
+        for (old_key, new_key) in &mapping {
+            assert!(new_key.id < 5);
+            assert_eq!(container.get(*old_key), None);
+            assert_eq!(container.get(*new_key), Some(&keys.iter().position(|key| key == old_key).unwrap()));
+        }
+
+        // regrowing reuses the released ids, which must not bring back any old key
+        for i in 0..200 {
+            container.add(i);
+        }
+        for key in &keys {
+            assert_eq!(container.get(*key), None);
+        }
+    }
+
+    #[test]
+    fn compact_keeps_retired_ids_retired() {
+        let mut container = StableIndexVec::<isize, u16>::default();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        container.set_generation(key1.id, u16::MAX);
+        container.remove(SIVKey::new(key1.id, u16::MAX));
+
+        let mapping: Vec<_> = container.compact().collect();
+        assert_eq!(mapping, [(key2, SIVKey::new(key2.id, key2.generation + 1))]);
+        assert_eq!(container.get(mapping[0].1), Some(&2));
+        assert_eq!(container.validate(), Ok(()));
+        for generation in [0, 1, u16::MAX] {
+            assert_eq!(container.get(SIVKey::new(key1.id, generation)), None);
+        }
+        assert_ne!(container.add(3).id, key1.id);
+    }
+
+    #[test]
+    fn compact_releases_metadata_after_retirement() {
+        let mut container = StableIndexVec::<u32, u16>::default();
+        for i in 0..70_000 {
+            let key = container.add(i);
+            container.remove(key);
+        }
+        let keys = container.add_many(0..10);
+        for &key in &keys[..9] {
+            container.remove(key);
+        }
+        assert_eq!(container.allocated_slots(), 11);
+
+        let mapping: Vec<_> = container.compact().collect();
+        assert_eq!(container.allocated_slots(), 2);
+        assert_eq!(container.validate(), Ok(()));
+        assert_eq!(mapping.len(), 1);
+        assert_eq!(container.get(mapping[0].1), Some(&9));
+        assert_eq!(container.get(keys[9]), None);
+
+        // regrowing must not bring back any key from before the compaction
+        for i in 0..20 {
+            container.add(i);
+        }
+        for &key in &keys {
+            assert_eq!(container.get(key), None);
+        }
+    }
+
+    #[test]
+    fn auto_compact_runs_after_retirement() {
+        let mut container = StableIndexVec::<u32, u16>::default();
+        let key = container.add(0);
+        container.set_generation(key.id, u16::MAX);
+        container.remove(SIVKey::new(key.id, u16::MAX));
+
+        container.set_auto_compact(Some(0.5));
+        let keys = container.add_many(0..8);
+        for &key in &keys[..7] {
+            container.remove(key);
+        }
+        assert!(container.allocated_slots() < 9);
+        let remap = container.take_compaction_remap();
+        let survivor = remap.iter().find(|(old, _)| *old == keys[7]).unwrap().1;
+        assert_eq!(container.get(survivor), Some(&7));
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn memory_usage_counts_all_vectors() {
+        let container = StableIndexVec::<u64>::new();
+        assert_eq!(container.memory_usage(), 0);
+
+        let container = StableIndexVec::<u64, u32>::with_capacity_in(10, Global);
+        assert_eq!(container.memory_usage(), 10 * 8 + 3 * 10 * 4);
+
+        let mut container = StableIndexVec::<[u8; 3]>::new();
+        let key = container.add([0; 3]);
+        container.remove(key);
+        assert!(container.memory_usage() >= 3 + 3 * std::mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn snapshot_keys_records_live_keys() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        container.remove(key1);
+        let key3 = container.add(3);
+
+        let snapshot = container.snapshot_keys();
+        assert_eq!(snapshot.len(), 2);
+        assert!(snapshot.contains(&key2));
+        assert!(snapshot.contains(&key3));
+
+        container.remove(key2);
+        container.add(4);
+        let still_live: Vec<_> = snapshot.iter().filter(|key| container.contains_key(**key)).collect();
+        assert_eq!(still_live, [&key3]);
+    }
+
+    #[test]
+    fn append_moves_elements_under_new_keys() {
+        let mut container1 = StableIndexVec::new();
+        let key1 = container1.add(1);
+        let key2 = container1.add(2);
+
+        let mut container2 = StableIndexVec::new();
+        let key3 = container2.add(3);
+        let key4 = container2.add(4);
+        container2.remove(key3);
+        let key5 = container2.add(5);
+
+        let mapping = container1.append(container2);
+        assert_eq!(mapping.len(), 2);
+        assert_eq!(container1.len(), 4);
+        assert_eq!(container1.get(key1), Some(&1));
+        assert_eq!(container1.get(key2), Some(&2));
+
+        for (old_key, new_key) in mapping {
+            assert!(![key1, key2].contains(&new_key));
+            let expected = if old_key == key4 { 4 } else { 5 };
+            assert!(old_key == key4 || old_key == key5);
+            assert_eq!(container1.get(new_key), Some(&expected));
+        }
+        assert_eq!(container1.validate(), Ok(()));
+    }
+
+    #[test]
+    fn filter_keeps_original_keys() {
+        let mut container = StableIndexVec::new();
+        let keys: Vec<_> = (0..10).map(|i| container.add(i)).collect();
+        container.remove(keys[3]);
+
+        let evens = container.filter(|_, value| value % 2 == 0);
+        assert_eq!(evens.len(), 5);
+        assert_eq!(evens.validate(), Ok(()));
+        assert_eq!(container.len(), 9);
+
+        for (i, key) in keys.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(evens.get(*key), Some(&i));
+            } else {
+                assert_eq!(evens.get(*key), None);
+            }
+        }
+
+        // adding to the filtered container must not revive the keys that were filtered out
+        let mut evens = evens;
+        for i in 0..10 {
+            evens.add(100 + i);
+        }
+        for key in keys.iter().skip(1).step_by(2) {
+            assert_eq!(evens.get(*key), None);
+        }
+    }
+
+    #[test]
+    fn filter_by_key() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add("a");
+        let key2 = container.add("b");
+
+        let filtered = container.filter(|key, _| key == key2);
+        assert_eq!(filtered.get(key1), None);
+        assert_eq!(filtered.get(key2), Some(&"b"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reindex_returns_translation_map() {
+        let mut container = StableIndexVec::new();
+        let keys: Vec<_> = (0..6).map(|i| container.add(i)).collect();
+        container.remove(keys[1]);
+        container.remove(keys[4]);
+
+        let translation = container.reindex();
+        assert_eq!(translation.len(), 4);
+        assert_eq!(container.slot_count(), 4);
+
+        let mut new_ids: Vec<_> = translation.values().map(|key| key.id).collect();
+        new_ids.sort();
+        assert_eq!(new_ids, [0, 1, 2, 3]);
+
+        for (i, old_key) in keys.iter().enumerate() {
+            assert_eq!(container.get(*old_key), None);
+            if let Some(new_key) = translation.get(old_key) {
+                assert_eq!(container.get(*new_key), Some(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn get_many_resolves_each_key() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+        container.remove(key2);
+
+        assert_eq!(container.get_many([key1, key2, key3, key1]), [Some(&1), None, Some(&3), Some(&1)]);
+        assert_eq!(container.get_many([]), [] as [Option<&isize>; 0]);
+    }
+
+    #[test]
+    fn first_and_last_follow_data_order() {
+        let mut container = StableIndexVec::new();
+        assert_eq!(container.first(), None);
+        assert_eq!(container.last(), None);
+
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+        assert_eq!(container.first(), Some((key1, &1)));
+        assert_eq!(container.last(), Some((key3, &3)));
+
+        // swap-pop moves the last element into the hole
+        container.remove(key1);
+        assert_eq!(container.first(), Some((key3, &3)));
+        assert_eq!(container.last(), Some((key2, &2)));
+    }
+
+    #[test]
+    fn pop_drains_one_element_at_a_time() {
+        let mut container = StableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+
+        assert_eq!(container.pop(), Some((key2, 2)));
+        assert_eq!(container.get(key2), None);
+        assert_eq!(container.pop(), Some((key1, 1)));
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.pop(), None);
+
+        let key3 = container.add(3);
+        assert!(![key1, key2].contains(&key3));
+    }
+
+    #[test]
+    fn clone_from_reuses_allocations() {
+        let mut source = StableIndexVec::new();
+        let keys: Vec<_> = (0..32).map(|i| source.add(i)).collect();
+        source.remove(keys[5]);
+
+        let mut target = StableIndexVec::with_capacity(64);
+        target.add(-1);
+        let data_ptr = target.as_ptr();
+        let index_ptr = target.index.as_ptr();
+
+        target.clone_from(&source);
+        assert_eq!(target.as_ptr(), data_ptr);
+        assert_eq!(target.index.as_ptr(), index_ptr);
+
+        let fresh = source.clone();
+        assert_eq!(target.debug_internals(), fresh.debug_internals());
+        assert_eq!(target.generation_floor, fresh.generation_floor);
+        assert!(target == fresh);
+        assert_eq!(target.add(99), fresh.clone().add(99));
+    }
+
+    #[test]
+    fn extend_from_slice_returns_keys_in_order() {
+        let mut container = StableIndexVec::new();
+        let existing = container.add(0);
+        container.remove(existing);
+
+        let keys = container.extend_from_slice(&[10, 20, 30]);
+        assert_eq!(keys.len(), 3);
+        assert!(container.capacity() >= 3);
+        for (key, value) in keys.iter().zip([10, 20, 30]) {
+            assert_eq!(container.get(*key), Some(&value));
+        }
+        assert_eq!(container.get(existing), None);
+        assert!(container.extend_from_slice(&[]).is_empty());
+        assert_eq!(container.len(), 3);
+    }
+
+    #[test]
+    fn dedup_keeps_first_occurrences() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 1, 3, 2, 1]);
+
+        assert_eq!(container.dedup(), 3);
+        assert_eq!(container.len(), 3);
+        assert_eq!(container.get(keys[0]), Some(&1));
+        assert_eq!(container.get(keys[1]), Some(&2));
+        assert_eq!(container.get(keys[3]), Some(&3));
+        for stale in [keys[2], keys[4], keys[5]] {
+            assert!(!container.contains_key(stale));
+        }
+        assert_eq!(container.dedup(), 0);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn count_matches_predicate() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(container.count(|el| el % 2 == 0), 3);
+
+        container.remove(keys[1]);
+        assert_eq!(container.count(|el| el % 2 == 0), 2);
+        assert_eq!(container.count(|_| true), container.len());
+        assert_eq!(StableIndexVec::<i32>::new().count(|_| true), 0);
+    }
+
+    #[test]
+    fn key_of_finds_first_match() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&["a", "b", "a"]);
+
+        assert_eq!(container.key_of(&"a"), Some(keys[0]));
+        assert_eq!(container.key_of(&"b"), Some(keys[1]));
+        assert_eq!(container.key_of(&"c"), None);
+
+        container.remove(keys[0]);
+        assert_eq!(container.key_of(&"a"), Some(keys[2]));
+    }
+
+    #[test]
+    fn data_index_of_points_into_slice() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[10, 20, 30]);
+        container.remove(keys[0]);
+
+        for &key in &keys[1..] {
+            let data_index = container.data_index_of(key).unwrap();
+            assert_eq!(Some(&container.as_slice()[data_index]), container.get(key));
+        }
+        assert_eq!(container.data_index_of(keys[0]), None);
+    }
+
+    #[test]
+    fn raw_entries_match_keys() {
+        let mut container = StableIndexVec::<char, u32>::default();
+        let keys = container.extend_from_slice(&['a', 'b', 'c']);
+        container.remove(keys[1]);
+        container.add('d');
+
+        let raw: Vec<_> = container.raw_entries().collect();
+        let keyed: Vec<_> = container
+            .iter()
+            .map(|(key, el)| (key.id as usize, key.generation as usize, el))
+            .collect();
+        assert_eq!(raw, keyed);
+        assert!(raw.contains(&(1, 1, &'d')));
+    }
+
+    #[test]
+    fn keys_sorted_ignores_removal_order() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[0, 1, 2, 3, 4]);
+        container.remove(keys[0]);
+        container.remove(keys[2]);
+
+        let sorted: Vec<_> = container.keys_sorted().collect();
+        assert_eq!(sorted, [keys[1], keys[3], keys[4]]);
+        assert_ne!(container.keys().collect::<Vec<_>>(), sorted);
+    }
+
+    #[test]
+    fn values_slice_has_no_holes() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3, 4, 5]);
+        container.remove(keys[1]);
+        container.remove(keys[3]);
+        assert_eq!(container.values_slice().len(), container.len());
+
+        container.add(6);
+        assert_eq!(container.values_slice().len(), container.len());
+        assert_eq!(container.values_slice(), container.as_slice());
+    }
+
+    #[test]
+    fn add_many_keys_all_resolve() {
+        let mut container = StableIndexVec::new();
+        let removed = container.add(0);
+        container.remove(removed);
+
+        let keys = container.add_many((1..=100).map(|i| i * 2));
+        assert_eq!(keys.len(), 100);
+        assert!(container.capacity() >= 100);
+        for (i, key) in keys.into_iter().enumerate() {
+            assert_eq!(container.get(key), Some(&((i + 1) * 2)));
+        }
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn add_tracked_reports_reuse() {
+        let mut container = StableIndexVec::new();
+        let (first, reused) = container.add_tracked('a');
+        assert!(!reused);
+        assert!(!container.add_tracked('b').1);
+
+        container.remove(first);
+        let (key, reused) = container.add_tracked('c');
+        assert!(reused);
+        assert_eq!(key.id, first.id);
+        assert!(!container.add_tracked('d').1);
+    }
+
+    #[test]
+    fn get_mut_modifies_element() {
+        let mut container = StableIndexVec::new();
+        let key = container.add(1);
+        *container.get_mut(key).unwrap() += 1;
+        assert_eq!(container.get(key), Some(&2));
+
+        container.remove(key);
+        assert_eq!(container.get_mut(key), None);
+    }
+
+    #[test]
+    fn get2_mut_borrows_two_elements() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3]);
+
+        let (a, b) = container.get2_mut(keys[0], keys[2]).unwrap();
+        core::mem::swap(a, b);
+        assert_eq!(container.get(keys[0]), Some(&3));
+        assert_eq!(container.get(keys[2]), Some(&1));
+
+        assert!(container.get2_mut(keys[1], keys[1]).is_none());
+        container.remove(keys[1]);
+        assert!(container.get2_mut(keys[0], keys[1]).is_none());
+        assert!(container.get_disjoint_mut([keys[0], keys[2]]).is_some());
+    }
+
+    #[test]
+    fn retain_returns_removed_count() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let mut seen = 0;
+        let removed = container.retain(|_, el| {
+            seen += 1;
+            el % 3 != 0
+        });
+        assert_eq!(removed, 2);
+        assert_eq!(seen, 6);
+        assert_eq!(container.get(keys[2]), None);
+        assert_eq!(container.get(keys[5]), None);
+        assert_eq!(container.get(keys[4]), Some(&5));
+
+        assert_eq!(container.retain(|key, _| key != keys[0]), 1);
+        assert_eq!(container.retain(|_, _| true), 0);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn drain_yields_keys_and_values() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&[1, 2, 3, 4]);
+        container.remove(keys[1]);
+
+        let mut drained: Vec<_> = container.drain().collect();
+        drained.sort();
+        assert_eq!(drained, [(keys[0], 1), (keys[2], 3), (keys[3], 4)]);
+        assert!(container.is_empty());
+        assert!(keys.iter().all(|&key| !container.contains_key(key)));
+        assert!(container.validate().is_ok());
+
+        let reused = container.add(5);
+        assert!(!keys.contains(&reused));
+        drop(container.drain());
+        assert!(!container.contains_key(reused));
+    }
+
+    #[test]
+    fn from_keyed_pairs_preserves_keys() {
+        let mut original = StableIndexVec::new();
+        let keys = original.extend_from_slice(&["a", "b", "c", "d"]);
+        original.remove(keys[1]);
+        let reused = original.add("e");
+
+        let pairs: Vec<_> = original.iter().map(|(key, &el)| (key, el)).collect();
+        let rebuilt = StableIndexVec::from_keyed_pairs(pairs).unwrap();
+        assert!(rebuilt == original);
+        assert!(rebuilt.validate().is_ok());
+        assert_eq!(rebuilt.get(keys[1]), None);
+        assert_eq!(rebuilt.get(reused), Some(&"e"));
+
+        let mut gaps = StableIndexVec::<char>::from_keyed_pairs([(SIVKey::new(3, 2), 'x')]).unwrap();
+        assert_eq!(gaps.slot_count(), 4);
+        assert!(gaps.validate().is_ok());
+        assert_ne!(gaps.add('y').id, 3);
+        assert_eq!(gaps.get(SIVKey::new(3, 2)), Some(&'x'));
+    }
+
+    #[test]
+    fn from_keyed_pairs_keeps_stale_keys_stale() {
+        let mut original = StableIndexVec::new();
+        let a = original.add("a");
+        let b = original.add("b");
+        original.remove(b);
+
+        let pairs: Vec<_> = original.iter().map(|(key, &el)| (key, el)).collect();
+        let mut rebuilt = StableIndexVec::<&str>::from_keyed_pairs(pairs).unwrap();
+        let z = rebuilt.add("z");
+        assert_eq!(rebuilt.get(b), None);
+        assert_eq!(rebuilt.get(z), Some(&"z"));
+        assert_eq!(rebuilt.get(a), Some(&"a"));
+        assert!(rebuilt.validate().is_ok());
+
+        // ids past the given ones may have been handed out before too
+        let fresh = rebuilt.add("fresh");
+        assert_eq!(rebuilt.get(SIVKey::new(fresh.id, 0)), None);
+    }
+
+    #[test]
+    fn from_keyed_pairs_retires_gaps_after_exhausted_generation() {
+        let pairs = [(SIVKey::new(2u16, u16::MAX), 'x')];
+        let mut container = StableIndexVec::<char, u16>::from_keyed_pairs(pairs).unwrap();
+        assert!(container.validate().is_ok());
+        assert_eq!(container.slot_count(), 3);
+        let added = container.add('y');
+        assert_eq!(added, SIVKey::new(3, u16::MAX));
+        assert_eq!(container.get(SIVKey::new(2, u16::MAX)), Some(&'x'));
+    }
+
+    #[test]
+    fn from_keyed_pairs_rejects_unallocatable_ids() {
+        let pairs = [(SIVKey::new(usize::MAX - 1, 0), 1)];
+        assert!(StableIndexVec::<i32>::from_keyed_pairs(pairs).is_err());
+    }
+
+    #[test]
+    fn from_keyed_pairs_rejects_collisions() {
+        let pairs = [(SIVKey::new(1, 0), 1), (SIVKey::new(0, 0), 2), (SIVKey::new(1, 3), 3)];
+        assert_eq!(
+            StableIndexVec::<i32>::from_keyed_pairs(pairs).unwrap_err(),
+            KeyCollision { key: SIVKey::new(1, 3) }
+        );
+
+        let unrepresentable = [(SIVKey::new(u16::MAX, 0), 1)];
+        assert!(StableIndexVec::<i32, u16>::from_keyed_pairs(unrepresentable).is_err());
+    }
+
+    #[test]
+    fn from_indexed_honors_ids() {
+        let (mut container, keys) = StableIndexVec::<&str>::from_indexed([(4, "d"), (0, "a"), (2, "c")]).unwrap();
+        assert_eq!(keys, [SIVKey::new(4, 0), SIVKey::new(0, 0), SIVKey::new(2, 0)]);
+        assert_eq!(container.get(SIVKey::new(2, 0)), Some(&"c"));
+        assert_eq!(container.slot_count(), 5);
+        assert!(container.validate().is_ok());
+
+        let added = container.add("b");
+        assert!(added.id == 1 || added.id == 3);
+        assert_eq!(container.get(SIVKey::new(added.id, 0)), None);
+    }
+
+    #[test]
+    fn from_indexed_rejects_bad_ids() {
+        assert_eq!(
+            StableIndexVec::<i32>::from_indexed([(1, 1), (0, 2), (1, 3)]).unwrap_err(),
+            IdError { id: 1 }
+        );
+        assert_eq!(
+            StableIndexVec::<i32, u16>::from_indexed([(u16::MAX as usize, 1)]).unwrap_err(),
+            IdError { id: u16::MAX as usize }
+        );
+        assert_eq!(
+            StableIndexVec::<i32>::from_indexed([(usize::MAX - 1, 1)]).unwrap_err(),
+            IdError { id: usize::MAX - 1 }
+        );
+    }
+
+    #[test]
+    fn works_without_partial_eq() {
+        #[derive(Clone, Debug)]
+        struct Opaque(u8);
+
+        let mut container = StableIndexVec::new();
+        let a = container.add(Opaque(1));
+        let b = container.add(Opaque(2));
+        container.remove(a);
+
+        let cloned = container.clone();
+        assert!(cloned.get(a).is_none());
+        assert_eq!(cloned.get(b).map(|el| el.0), Some(2));
+        assert_eq!(cloned.len(), 1);
+        assert!(cloned.validate().is_ok());
+    }
+
+    #[test]
+    fn alternate_debug_shows_generations() {
+        let mut container = StableIndexVec::new();
+        let key = container.add(1);
+        container.remove(key);
+        container.add(2);
+
+        assert_eq!(format!("{container:?}"), "StableIndexVec { 0: 2 }");
+        assert_eq!(format!("{container:#?}"), "StableIndexVec {\n    0@1: 2,\n}");
+    }
+
+    #[test]
+    fn slot_metrics_track_fragmentation() {
+        let mut container = StableIndexVec::new();
+        assert_eq!(container.load_factor(), 1.0);
+
+        let keys = container.add_many(0..8);
+        assert_eq!(container.live_count(), 8);
+        assert_eq!(container.allocated_slots(), 8);
+        assert_eq!(container.load_factor(), 1.0);
+
+        for &key in &keys[..6] {
+            container.remove(key);
+        }
+        assert_eq!(container.live_count(), 2);
+        assert_eq!(container.allocated_slots(), 8);
+        assert_eq!(container.load_factor(), 0.25);
+
+        container.add(8);
+        assert_eq!(container.live_count(), 3);
+        assert_eq!(container.allocated_slots(), 8);
+        assert_eq!(container.load_factor(), 0.375);
+
+        let _ = container.compact();
+        assert_eq!(container.allocated_slots(), 3);
+        assert_eq!(container.load_factor(), 1.0);
+    }
+
+    #[test]
+    fn fragmentation_is_unused_fraction() {
+        let mut container = StableIndexVec::new();
+        assert_eq!(container.fragmentation(), 0.0);
+        let keys = container.add_many(0..4);
+        container.remove(keys[0]);
+        assert_eq!(container.fragmentation(), 0.25);
+    }
+
+    #[test]
+    fn auto_compact_is_off_by_default() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..10);
+        for &key in &keys[..9] {
+            container.remove(key);
+        }
+        assert_eq!(container.get(keys[9]), Some(&9));
+        assert_eq!(container.allocated_slots(), 10);
+        assert!(container.take_compaction_remap().is_empty());
+    }
+
+    #[test]
+    fn auto_compact_records_remap() {
+        let mut container = StableIndexVec::new();
+        container.set_auto_compact(Some(0.5));
+        let keys = container.add_many(0..8);
+
+        for &key in &keys[..4] {
+            container.remove(key);
+        }
+        assert_eq!(container.allocated_slots(), 8);
+
+        // the fifth removal pushes fragmentation past 0.5, and a later one compacts again
+        for &key in &keys[4..7] {
+            container.remove(key);
+        }
+        let added = container.add(8);
+        assert!(container.allocated_slots() < 8);
+
+        let remap = container.take_compaction_remap();
+        let survivor = remap.iter().find(|(old, _)| *old == keys[7]).unwrap().1;
+        assert_eq!(container.get(survivor), Some(&7));
+        assert_eq!(container.get(keys[7]), None);
+        assert_eq!(container.get(added), Some(&8));
+        assert!(container.take_compaction_remap().is_empty());
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn batch_removals_compact_once_at_the_end() {
+        let setup = || {
+            let mut container = StableIndexVec::new();
+            container.set_auto_compact(Some(0.3));
+            let keys = container.add_many(0..10);
+            (container, keys)
+        };
+
+        let (mut container, keys) = setup();
+        let removed = container.remove_where(|el| el % 2 == 0);
+        let removed_keys: Vec<_> = removed.iter().map(|&(key, _)| key).collect();
+        let expected: Vec<_> = keys.iter().copied().step_by(2).collect();
+        assert_eq!(removed_keys.len(), 5);
+        assert!(removed_keys.iter().all(|key| expected.contains(key)));
+        let remap = container.take_compaction_remap();
+        assert_eq!(remap.len(), 5);
+        for (old, new) in remap {
+            let value = keys.iter().position(|&key| key == old).unwrap();
+            assert_eq!(value % 2, 1);
+            assert_eq!(container.get(new), Some(&value));
+        }
+
+        let (mut container, keys) = setup();
+        assert_eq!(container.retain_keys(|key| key.id >= 6), 6);
+        let remap = container.take_compaction_remap();
+        assert_eq!(remap.len(), 4);
+        assert!(remap.iter().all(|(old, _)| keys[6..].contains(old)));
+        assert_eq!(container.allocated_slots(), 4);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn auto_compact_composes_pending_remaps() {
+        let mut container = StableIndexVec::new();
+        container.set_auto_compact(Some(0.4));
+        let keys = container.add_many(0..10);
+
+        for &key in &keys[..8] {
+            container.remove(key);
+            assert!(container.fragmentation() <= 0.4);
+        }
+        assert!(container.dedup() == 0);
+
+        let remap = container.take_compaction_remap();
+        for (i, &key) in keys.iter().enumerate().skip(8) {
+            let new_key = remap.iter().find(|(old, _)| *old == key).unwrap().1;
+            assert_eq!(container.get(new_key), Some(&i));
+        }
+    }
+
+    #[test]
+    fn reserve_ids_leaves_data_capacity_alone() {
+        let mut container = StableIndexVec::<u64>::new();
+        container.add(1);
+        let data_capacity = container.capacity();
+
+        container.reserve_ids(100);
+        assert!(container.index.capacity() >= 101);
+        assert!(container.generations.capacity() >= 101);
+        assert!(container.ids.capacity() >= 101);
+        assert_eq!(container.capacity(), data_capacity);
+    }
+
+    #[test]
+    fn shrink_metadata_keeps_data_capacity() {
+        let mut container = StableIndexVec::with_capacity(64);
+        let keys = container.add_many(0..4);
+        container.reserve_ids(100);
+
+        container.shrink_metadata();
+        assert_eq!(container.index.capacity(), 4);
+        assert_eq!(container.generations.capacity(), 4);
+        assert_eq!(container.ids.capacity(), 4);
+        assert!(container.capacity() >= 64);
+        assert_eq!(container.get(keys[3]), Some(&3));
+    }
+
+    #[test]
+    fn key_for_maps_positions_to_keys() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(10..15);
+        container.remove(keys[1]);
+
+        let position = container.as_slice().iter().position(|&el| el == 14).unwrap();
+        assert_eq!(container.key_for(position), Some(keys[4]));
+        assert_eq!(container.key_for(0), Some(keys[0]));
+        assert_eq!(container.key_for(container.len()), None);
+    }
+
+    #[test]
+    fn iter_with_index_matches_slice_positions() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..6);
+        container.remove(keys[2]);
+
+        let mut count = 0;
+        for (key, data_index, el) in container.iter_with_index() {
+            assert_eq!(&container.as_slice()[data_index], el);
+            assert_eq!(container.key_for(data_index), Some(key));
+            count += 1;
+        }
+        assert_eq!(count, container.len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn content_hash_ignores_internal_order() {
+        let mut a = StableIndexVec::new();
+        let keys = a.add_many(0..5);
+        let mut b = a.clone();
+
+        // removing and re-adding moves elements around differently in each container
+        a.remove(keys[0]);
+        a.remove(keys[1]);
+        b.remove(keys[1]);
+        b.remove(keys[0]);
+        assert_ne!(a.values_slice(), b.values_slice());
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        *b.get_mut(keys[2]).unwrap() = 10;
+        assert_ne!(a.content_hash(), b.content_hash());
+    }
+
+    #[test]
+    fn equals_ignoring_keys_compares_values() {
+        let mut a = StableIndexVec::new();
+        a.add_many([1, 2, 2, 3]);
+
+        let mut b = StableIndexVec::new();
+        let removed = b.add(0);
+        b.add_many([2, 3]);
+        b.remove(removed);
+        b.add_many([2, 1]);
+
+        assert!(a != b);
+        assert!(a.equals_ignoring_keys(&b));
+        assert!(b.equals_ignoring_keys(&a));
+
+        b.add(4);
+        assert!(!a.equals_ignoring_keys(&b));
+        a.add(3);
+        assert!(!a.equals_ignoring_keys(&b));
+    }
+
+    #[test]
+    fn transaction_rolls_back_on_err() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..3);
+        let before = container.clone();
+
+        let result: Result<(), &str> = container.transaction(|c| {
+            c.remove(keys[0]);
+            c.add(10);
+            Err("abort")
+        });
+        assert_eq!(result, Err("abort"));
+        assert!(container == before);
+        assert_eq!(container.get(keys[0]), Some(&0));
+
+        let added = container.transaction(|c| {
+            c.remove(keys[1]);
+            Ok::<_, ()>(c.add(20))
+        });
+        assert_eq!(container.get(added.unwrap()), Some(&20));
+        assert_eq!(container.get(keys[1]), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn transaction_rolls_back_on_panic() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..3);
+        let before = container.clone();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            container.transaction(|c| -> Result<(), ()> {
+                c.remove(keys[2]);
+                panic!("failed mid-update");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(container == before);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn panicking_drop_leaves_container_valid() {
+        #[derive(Debug)]
+        struct Bomb(bool);
+
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                if self.0 && !std::thread::panicking() {
+                    panic!("bomb went off");
+                }
+            }
+        }
+
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many((0..6).map(|i| Bomb(i % 2 == 1)));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(container.remove(keys[1]))));
+        assert!(result.is_err());
+        assert!(container.validate().is_ok());
+        assert!(!container.contains_key(keys[1]));
+        assert_eq!(container.len(), 5);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| container.retain(|_, bomb| !bomb.0)));
+        assert!(result.is_err());
+        assert!(container.validate().is_ok());
+        assert_eq!(container.len(), 4);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| container.drain().count()));
+        assert!(result.is_err());
+        assert!(container.validate().is_ok());
+        assert!(container.is_empty());
+        assert!(keys.iter().all(|&key| !container.contains_key(key)));
+
+        let key = container.add(Bomb(false));
+        assert!(container.pop().is_some_and(|(popped, _)| popped == key));
+    }
+
+    #[test]
+    fn add_stays_consistent_when_allocation_fails() {
+        for budget in 0..8 {
+            let alloc = FailingAllocator(std::rc::Rc::new(std::cell::Cell::new(budget)));
+            let mut container = StableIndexVec::<u32, usize, _>::new_in(alloc);
+            let mut keys = Vec::new();
+
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                for i in 0..100 {
+                    keys.push(container.add(i));
+                }
+            }));
+            assert!(result.is_err());
+            assert!(container.validate().is_ok(), "budget {budget}: {:?}", container.validate());
+            assert_eq!(container.len(), keys.len());
+            assert_eq!(container.slot_count(), keys.len());
+            for (i, &key) in keys.iter().enumerate() {
+                assert_eq!(container.get(key), Some(&(i as u32)));
+            }
+        }
+    }
+
+    #[test]
+    fn shrink_to_keeps_a_floor() {
+        let mut container = StableIndexVec::with_capacity(100);
+        let keys = container.add_many(0..20);
+        for &key in &keys[..15] {
+            container.remove(key);
+        }
+
+        container.shrink_to(10);
+        assert!(container.capacity() >= 10 && container.capacity() < 100);
+        assert!(container.index.capacity() >= 20);
+        assert!(container.ids.capacity() >= 20);
+
+        container.shrink_to(0);
+        assert!(container.capacity() >= container.len());
+        assert!(container.generations.capacity() >= 20);
+        assert_eq!(container.get(keys[19]), Some(&19));
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn get_or_add_interns_values() {
+        let mut container = StableIndexVec::new();
+        let hello = container.get_or_add("hello");
+        let world = container.get_or_add("world");
+        assert_ne!(hello, world);
+        assert_eq!(container.get_or_add("hello"), hello);
+        assert_eq!(container.len(), 2);
+
+        container.remove(hello);
+        let again = container.get_or_add("hello");
+        assert_ne!(again, hello);
+        assert_eq!(container.get(again), Some(&"hello"));
+    }
+
+    #[test]
+    fn regenerate_revokes_old_key() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..3);
+
+        let new_key = container.regenerate(keys[1]).unwrap();
+        assert_ne!(new_key, keys[1]);
+        assert_eq!(container.get(keys[1]), None);
+        assert_eq!(container.get(new_key), Some(&1));
+        assert_eq!(container.regenerate(keys[1]), None);
+        assert_eq!(container.as_slice(), [0, 1, 2]);
+
+        container.remove(new_key);
+        let reused = container.add(3);
+        assert_ne!(reused, keys[1]);
+        assert_ne!(reused, new_key);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn regenerate_retires_exhausted_ids() {
+        let mut container = StableIndexVec::<char, u16>::default();
+        let key = container.add('a');
+        let other = container.add('b');
+        container.set_generation(key.id, u16::MAX - 1);
+        let key = SIVKey::new(key.id, u16::MAX - 1);
+
+        let last = container.regenerate(key).unwrap();
+        assert_eq!(last.generation, u16::MAX);
+        let moved = container.regenerate(last).unwrap();
+        assert_ne!(moved.id, key.id);
+        assert_eq!(container.get(last), None);
+        assert_eq!(container.get(moved), Some(&'a'));
+        assert_eq!(container.get(other), Some(&'b'));
+        assert!(container.validate().is_ok());
+
+        container.remove(moved);
+        assert!(container.validate().is_ok());
+        assert_ne!(container.add('c').id, key.id);
+    }
+
+    #[test]
+    fn invalidate_rotates_handle() {
+        let mut container = StableIndexVec::new();
+        let key = container.add(String::from("secret"));
+
+        let rotated = container.invalidate(key).unwrap();
+        assert!(!container.contains_key(key));
+        assert_eq!(container.get(rotated).map(String::as_str), Some("secret"));
+        assert_eq!(container.invalidate(key), None);
+        assert_eq!(container.len(), 1);
+    }
+
+    #[test]
+    fn into_iter_moves_out_keys_and_values() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(["a", "b", "c"].map(String::from));
+        container.remove(keys[0]);
+        let expected: Vec<_> = container.iter().map(|(key, el)| (key, el.clone())).collect();
+
+        let iter = container.clone().into_iter();
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.collect::<Vec<_>>(), expected);
+
+        let round_trip = StableIndexVec::from_keyed_pairs(container.clone()).unwrap();
+        assert!(round_trip == container);
+
+        assert_eq!(container.clone().into_keys().collect::<Vec<_>>(), [keys[2], keys[1]]);
+        assert_eq!(container.into_values().collect::<Vec<_>>(), ["c", "b"]);
+    }
+
+    #[test]
+    fn into_boxed_slice_keeps_values() {
+        let mut container = StableIndexVec::with_capacity(16);
+        let keys = container.add_many(0..4);
+        container.remove(keys[0]);
+        let expected = container.as_slice().to_vec();
+
+        let boxed = container.into_boxed_slice();
+        assert_eq!(&*boxed, expected.as_slice());
+    }
+
+    #[test]
+    fn zip_yields_shared_keys() {
+        let mut positions = StableIndexVec::new();
+        let keys = positions.add_many([(0, 0), (1, 1), (2, 2), (3, 3)]);
+
+        let names = [(keys[3], "d"), (SIVKey::new(7, 0), "x"), (keys[1], "b")];
+        let names = StableIndexVec::<&str>::from_keyed_pairs(names).unwrap();
+
+        let zipped: Vec<_> = positions.zip(&names).collect();
+        assert_eq!(zipped, [(keys[1], &(1, 1), &"b"), (keys[3], &(3, 3), &"d")]);
+    }
+
+    #[test]
+    fn key_set_operations() {
+        let mut a = StableIndexVec::new();
+        let keys = a.add_many(0..5);
+        let mut b = a.clone();
+        a.remove(keys[0]);
+        b.remove(keys[4]);
+        b.remove(keys[2]);
+        let added = b.add(5);
+
+        let sorted = |keys: &mut dyn Iterator<Item = SIVKey>| {
+            let mut keys: Vec<_> = keys.collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(sorted(&mut a.key_intersection(&b)), [keys[1], keys[3]]);
+        assert_eq!(sorted(&mut a.key_difference(&b)), [keys[2], keys[4]]);
+        assert_eq!(sorted(&mut b.key_difference(&a)), sorted(&mut [keys[0], added].into_iter()));
+        assert_eq!(
+            sorted(&mut a.key_union(&b)),
+            sorted(&mut [keys[0], keys[1], keys[2], keys[3], keys[4], added].into_iter())
+        );
+    }
+
+    #[test]
+    fn retain_keys_filters_by_key() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..6);
+        let keep = [keys[1], keys[4]];
+
+        assert_eq!(container.retain_keys(|key| keep.contains(&key)), 4);
+        assert_eq!(container.len(), 2);
+        assert!(keep.iter().all(|&key| container.contains_key(key)));
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn remove_where_extracts_matches() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many([5, 2, 8, 1, 9, 4]);
+
+        let mut tested = 0;
+        let mut removed = container.remove_where(|&el| {
+            tested += 1;
+            el > 4
+        });
+        removed.sort();
+        assert_eq!(tested, 6);
+        assert_eq!(removed, [(keys[0], 5), (keys[2], 8), (keys[4], 9)]);
+        assert_eq!(container.len(), 3);
+        assert!(removed.iter().all(|&(key, _)| !container.contains_key(key)));
+        assert!(container.remove_where(|_| false).is_empty());
+    }
+
+    #[test]
+    fn try_add_respects_limit() {
+        let mut container = StableIndexVec::new();
+        let first = container.try_add('a', 2).unwrap();
+        container.try_add('b', 2).unwrap();
+        assert_eq!(container.try_add('c', 2), Err('c'));
+        assert_eq!(container.len(), 2);
+
+        container.remove(first);
+        assert!(container.try_add('c', 2).is_ok());
+        assert!(container.try_add('d', 3).is_ok());
+    }
+
+    #[test]
+    fn to_vec_sorted_by_key_ignores_removal_order() {
+        let mut container = StableIndexVec::new();
+        let keys = container.extend_from_slice(&['a', 'b', 'c', 'd']);
+        container.remove(keys[0]);
+        let e = container.add('e');
+
+        assert_eq!(container.to_vec_sorted_by_key(), [(e, &'e'), (keys[1], &'b'), (keys[2], &'c'), (keys[3], &'d')]);
+    }
+
+    #[test]
+    fn iter_sorted_is_reproducible() {
+        let mut forward = StableIndexVec::new();
+        let mut backward = StableIndexVec::new();
+        let keys = forward.extend_from_slice(&[1, 2, 3, 4]);
+        backward.extend_from_slice(&[1, 2, 3, 4]);
+        forward.remove(keys[0]);
+        forward.remove(keys[1]);
+        backward.remove(keys[1]);
+        backward.remove(keys[0]);
+
+        assert_ne!(forward.values_slice(), backward.values_slice());
+        assert!(forward.iter_sorted().eq(backward.iter_sorted()));
+        assert_eq!(forward.iter_sorted().map(|(_, &el)| el).collect::<Vec<_>>(), [3, 4]);
+    }
+
+    #[test]
+    fn clone_preserves_keys() {
+        let mut rng = XorShift(0x5eed);
+        let mut original = StableIndexVec::new();
+        let mut keys = Vec::new();
+        for i in 0..200 {
+            if !keys.is_empty() && rng.below(3) == 0 {
+                original.remove(keys[rng.below(keys.len())]);
+            } else {
+                keys.push(original.add(i));
+            }
+        }
+
+        let mut cloned = original.clone();
+        let mut cloned_from = StableIndexVec::new();
+        cloned_from.clone_from(&original);
+        for copy in [&cloned, &cloned_from] {
+            for &key in &keys {
+                assert_eq!(copy.get(key), original.get(key));
+            }
+        }
+
+        let next = cloned.add(-1);
+        assert_eq!(cloned_from.add(-1), next);
+        assert_eq!(original.add(-1), next);
+    }
+
+    #[test]
+    fn remap_ids_moves_keys() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(['a', 'b', 'c']);
+        container.remove(keys[1]);
+        let reused = container.add('d');
+
+        container.remap_ids(|id| id * 2 + 1).unwrap();
+        assert!(container.validate().is_ok());
+        assert_eq!(container.slot_count(), 6);
+        assert_eq!(container.get(keys[0]), None);
+        assert_eq!(container.get(SIVKey::new(1, keys[0].generation)), Some(&'a'));
+        assert_eq!(container.get(SIVKey::new(3, reused.generation)), Some(&'d'));
+        assert_eq!(container.get(SIVKey::new(3, keys[1].generation)), None);
+        assert_eq!(container.get(SIVKey::new(5, keys[2].generation)), Some(&'c'));
+
+        let added = container.add('e');
+        assert!([0, 2, 4].contains(&added.id));
+    }
+
+    #[test]
+    fn remap_ids_rejects_collisions() {
+        let mut container = StableIndexVec::new();
+        container.add_many([1, 2, 3]);
+        let before = container.clone();
+
+        assert_eq!(container.remap_ids(|id| id / 2), Err(RemapError { id: 0 }));
+        assert_eq!(container.remap_ids(|_| usize::MAX), Err(RemapError { id: usize::MAX }));
+        assert_eq!(container.debug_internals(), before.debug_internals());
+    }
+
+    #[test]
+    fn new_is_const() {
+        const EMPTY: StableIndexVec<u8> = StableIndexVec::new();
+        static SHARED: std::sync::Mutex<StableIndexVec<&str>> = std::sync::Mutex::new(StableIndexVec::new());
+
+        assert!(EMPTY.is_empty());
+        let key = SHARED.lock().unwrap().add("static");
+        assert_eq!(SHARED.lock().unwrap().get(key), Some(&"static"));
+    }
+
+    /// Never called; it only has to compile. A field that isn't `Send` or `Sync` (an `Rc`, a raw pointer, a
+    /// `Cell`) would break these bounds
+    fn _assert_auto_traits() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+        fn is_copy<T: Copy + Send + Sync>() {}
+
+        is_send::<StableIndexVec<i32>>();
+        is_sync::<StableIndexVec<i32>>();
+        is_send::<StableIndexVec<String, u32>>();
+        is_sync::<StableIndexVec<String, u32>>();
+        is_send::<IntoIter<String, usize, Global>>();
+        is_sync::<Iter<'static, String>>();
+        is_send::<LruStableIndexVec<String>>();
+        is_sync::<LruStableIndexVec<String>>();
+        #[cfg(feature = "std")]
+        {
+            is_send::<SyncStableIndexVec<String>>();
+            is_sync::<SyncStableIndexVec<String>>();
+            is_send::<SharedStableIndexVec<String>>();
+            is_sync::<SharedStableIndexVec<String>>();
+        }
+        is_copy::<SIVKey>();
+        is_copy::<SIVKey<u16>>();
+    }
+
+    #[test]
+    fn get_or_insert_hits_and_misses() {
+        let mut container = StableIndexVec::<u32>::new();
+        let key = container.add(5);
+
+        let (hit, el) = container.get_or_insert_default(key);
+        *el += 1;
+        assert_eq!(hit, key);
+        assert_eq!(container.get(key), Some(&6));
+
+        container.remove(key);
+        let (missed, el) = container.get_or_insert_default(key);
+        assert_eq!(*el, 0);
+        assert_ne!(missed, key);
+        assert_eq!(container.len(), 1);
+
+        let (hit, el) = container.get_or_insert_with(missed, || unreachable!());
+        assert_eq!((hit, *el), (missed, 0));
+        let (added, el) = container.get_or_insert_with(key, || 9);
+        assert_eq!(*el, 9);
+        assert_eq!(container.get(added), Some(&9));
+    }
 }
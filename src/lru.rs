@@ -0,0 +1,329 @@
+use alloc::boxed::Box;
+use allocator_api2::vec::Vec;
+
+use crate::{Iter, SIVKey, StableIndexVec};
+
+/// Marks the end of the recency list
+const NONE: usize = usize::MAX;
+
+/// A StableIndexVec that acts as a bounded cache, evicting the least recently used element when it's full.
+///
+/// Recency is an intrusive doubly-linked list over ids, so `get`, `get_mut`, `add` and `remove` stay O(1).
+/// `get` and `get_mut` count as a use; `peek` reads without touching recency.
+pub struct LruStableIndexVec<T> {
+    inner: StableIndexVec<T>,
+    max: usize,
+    /// Per id, the next more recently used id
+    prev: Vec<usize>,
+    /// Per id, the next less recently used id
+    next: Vec<usize>,
+    /// Most recently used id
+    head: usize,
+    /// Least recently used id, the next to be evicted
+    tail: usize,
+    /// Receives elements evicted by `add` instead of `add` returning them
+    on_evict: Option<OnEvict<T>>,
+}
+
+/// The boxed eviction callback. It's only `Send`, but the cache stays `Sync` because nothing reaches the callback
+/// through a shared reference
+struct OnEvict<T>(Box<dyn FnMut(SIVKey, T) + Send>);
+
+// SAFETY: a `&OnEvict` gives no access to the callback; it's only called through `&mut`, which is exclusive
+unsafe impl<T> Sync for OnEvict<T> {}
+
+/// Clones the elements and recency order. The eviction callback can't be cloned, so the clone has none
+impl<T: Clone> Clone for LruStableIndexVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            max: self.max,
+            prev: self.prev.clone(),
+            next: self.next.clone(),
+            head: self.head,
+            tail: self.tail,
+            on_evict: None,
+        }
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for LruStableIndexVec<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LruStableIndexVec")
+            .field("inner", &self.inner)
+            .field("max", &self.max)
+            .field("on_evict", &self.on_evict.is_some())
+            .finish()
+    }
+}
+
+impl<T> LruStableIndexVec<T> {
+    /// Creates a new empty cache that holds at most `max` elements. Panics if `max` is 0
+    pub fn with_max_capacity(max: usize) -> Self {
+        assert!(max > 0, "LruStableIndexVec needs room for at least one element");
+        Self {
+            inner: StableIndexVec::new(),
+            max,
+            prev: Vec::new(),
+            next: Vec::new(),
+            head: NONE,
+            tail: NONE,
+            on_evict: None,
+        }
+    }
+
+    /// Registers a callback that receives every element `add` evicts, with its key, before the slot is reused.
+    /// While one is registered, `add` hands evicted elements to it instead of returning them. The callback must be
+    /// `Send` so the cache can still move between threads
+    pub fn set_on_evict(&mut self, on_evict: impl FnMut(SIVKey, T) + Send + 'static) {
+        self.on_evict = Some(OnEvict(Box::new(on_evict)));
+    }
+
+    /// Removes the eviction callback, so `add` returns evicted elements again
+    pub fn clear_on_evict(&mut self) {
+        self.on_evict = None;
+    }
+
+    /// Gets the most elements the cache will hold
+    pub fn max_capacity(&self) -> usize {
+        self.max
+    }
+
+    /// Gets the number of elements in the cache
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks if the cache has no elements
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Gets a reference to an element by its key and marks it as the most recently used
+    pub fn get(&mut self, key: SIVKey) -> Option<&T> {
+        self.touch(key)?;
+        self.inner.get(key)
+    }
+
+    /// Gets a mutable reference to an element by its key and marks it as the most recently used
+    pub fn get_mut(&mut self, key: SIVKey) -> Option<&mut T> {
+        self.touch(key)?;
+        self.inner.get_mut(key)
+    }
+
+    /// Gets a reference to an element by its key without marking it as used
+    pub fn peek(&self, key: SIVKey) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Checks if the key refers to a live element, without marking it as used
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Adds an element as the most recently used one and returns its key. If the cache was full, the least
+    /// recently used element is evicted first and returned with its key, or passed to the `on_evict` callback
+    pub fn add(&mut self, el: T) -> (SIVKey, Option<(SIVKey, T)>) {
+        let evicted = if self.inner.len() >= self.max { self.pop_lru() } else { None };
+        let evicted = match (evicted, &mut self.on_evict) {
+            (Some((key, el)), Some(OnEvict(on_evict))) => {
+                on_evict(key, el);
+                None
+            }
+            (evicted, _) => evicted,
+        };
+
+        let key = self.inner.add(el);
+        let id = key.id;
+        if id >= self.prev.len() {
+            self.prev.resize(id + 1, NONE);
+            self.next.resize(id + 1, NONE);
+        }
+        self.push_front(id);
+        (key, evicted)
+    }
+
+    /// Removes an element from the cache by its key
+    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+        let el = self.inner.remove(key)?;
+        self.unlink(key.id);
+        Some(el)
+    }
+
+    /// Removes and returns the least recently used element with its key
+    pub fn pop_lru(&mut self) -> Option<(SIVKey, T)> {
+        if self.tail == NONE {
+            return None;
+        }
+        let key = self.live_key(self.tail);
+        let el = self.remove(key)?;
+        Some((key, el))
+    }
+
+    /// Returns an iterator over the keys from most to least recently used
+    pub fn keys_by_recency(&self) -> impl Iterator<Item = SIVKey> + '_ {
+        let mut id = self.head;
+        core::iter::from_fn(move || {
+            if id == NONE {
+                return None;
+            }
+            let key = self.live_key(id);
+            id = self.next[id];
+            Some(key)
+        })
+    }
+
+    /// Returns an iterator over all key-value pairs in the cache, in internal order. This doesn't mark anything as used
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.inner.iter()
+    }
+
+    /// Builds the current key of an id in the recency list, which is always live
+    fn live_key(&self, id: usize) -> SIVKey {
+        SIVKey::new(id, self.inner.generations[id])
+    }
+
+    /// Moves a live key's id to the front of the recency list
+    fn touch(&mut self, key: SIVKey) -> Option<()> {
+        if !self.inner.contains_key(key) {
+            return None;
+        }
+        self.unlink(key.id);
+        self.push_front(key.id);
+        Some(())
+    }
+
+    fn push_front(&mut self, id: usize) {
+        self.prev[id] = NONE;
+        self.next[id] = self.head;
+        match self.head {
+            NONE => self.tail = id,
+            head => self.prev[head] = id,
+        }
+        self.head = id;
+    }
+
+    fn unlink(&mut self, id: usize) {
+        let (prev, next) = (self.prev[id], self.next[id]);
+        match prev {
+            NONE => self.head = next,
+            prev => self.next[prev] = next,
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => self.prev[next] = prev,
+        }
+        self.prev[id] = NONE;
+        self.next[id] = NONE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruStableIndexVec::with_max_capacity(3);
+        let a = cache.add("a").0;
+        let b = cache.add("b").0;
+        let c = cache.add("c").0;
+
+        assert_eq!(cache.get(a), Some(&"a"));
+        let (d, evicted) = cache.add("d");
+        assert_eq!(evicted, Some((b, "b")));
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains_key(b));
+
+        assert_eq!(cache.keys_by_recency().collect::<Vec<_>>(), [d, a, c]);
+        *cache.get_mut(c).unwrap() = "C";
+        assert_eq!(cache.add("e").1, Some((a, "a")));
+        assert_eq!(cache.peek(c), Some(&"C"));
+    }
+
+    #[test]
+    fn peek_does_not_touch_recency() {
+        let mut cache = LruStableIndexVec::with_max_capacity(2);
+        let a = cache.add(1).0;
+        cache.add(2);
+
+        assert_eq!(cache.peek(a), Some(&1));
+        assert_eq!(cache.add(3).1, Some((a, 1)));
+    }
+
+    #[test]
+    fn remove_unlinks_from_recency_list() {
+        let mut cache = LruStableIndexVec::with_max_capacity(3);
+        let keys: Vec<_> = (0..3).map(|i| cache.add(i).0).collect();
+
+        assert_eq!(cache.remove(keys[1]), Some(1));
+        assert_eq!(cache.get(keys[1]), None);
+        assert_eq!(cache.keys_by_recency().collect::<Vec<_>>(), [keys[2], keys[0]]);
+
+        let (reused, evicted) = cache.add(3);
+        assert_eq!(evicted, None);
+        assert_eq!(cache.pop_lru(), Some((keys[0], 0)));
+        assert_eq!(cache.keys_by_recency().collect::<Vec<_>>(), [reused, keys[2]]);
+        assert_eq!(cache.pop_lru().map(|(_, el)| el), Some(2));
+        assert_eq!(cache.pop_lru().map(|(_, el)| el), Some(3));
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn churn_keeps_list_consistent() {
+        let mut cache = LruStableIndexVec::with_max_capacity(4);
+        let mut keys = Vec::new();
+        for i in 0..100 {
+            let (key, _) = cache.add(i);
+            keys.push(key);
+            if i % 3 == 0 {
+                cache.get(keys[i / 2]);
+            }
+            if i % 7 == 0 {
+                cache.remove(keys[i]);
+            }
+            assert_eq!(cache.keys_by_recency().count(), cache.len());
+            assert!(cache.len() <= 4);
+        }
+    }
+
+    #[test]
+    fn on_evict_receives_evicted_elements() {
+        let flushed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut cache = LruStableIndexVec::with_max_capacity(2);
+        let sink = flushed.clone();
+        cache.set_on_evict(move |key, el| sink.lock().unwrap().push((key, el)));
+
+        let a = cache.add('a').0;
+        let b = cache.add('b').0;
+        cache.get(a);
+        assert_eq!(cache.add('c').1, None);
+        assert_eq!(cache.add('d').1, None);
+        assert_eq!(*flushed.lock().unwrap(), [(b, 'b'), (a, 'a')]);
+        assert!(cache.clone().add('e').1.is_some());
+
+        cache.clear_on_evict();
+        assert!(cache.add('e').1.is_some());
+        assert_eq!(flushed.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn on_evict_accepts_callbacks_that_are_not_sync() {
+        let flushed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut cache = LruStableIndexVec::with_max_capacity(1);
+        let sink = flushed.clone();
+        let evictions = std::cell::Cell::new(0);
+        cache.set_on_evict(move |_, el| {
+            evictions.set(evictions.get() + 1);
+            sink.lock().unwrap().push((evictions.get(), el));
+        });
+
+        for el in ['a', 'b', 'c'] {
+            cache.add(el);
+        }
+        let cache = std::thread::spawn(move || cache).join().unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(*flushed.lock().unwrap(), [(1, 'a'), (2, 'b')]);
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::string::ToString;
+
+use crate::{Iter, SIVKey, StableIndexVec};
+
+/// A StableIndexVec that always iterates in insertion order.
+///
+/// The base type removes with swap-pop, which is O(1) but moves the last element into the hole,
+/// so its iteration order changes after removals. This wrapper removes with `remove_stable` instead,
+/// which keeps the order intact at the cost of O(n) removals. Lookups and adds are just as fast as the base type.
+#[derive(Default, Clone)]
+pub struct OrderedStableIndexVec<T> {
+    inner: StableIndexVec<T>,
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for OrderedStableIndexVec<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug_string = f.debug_struct("OrderedStableIndexVec");
+        for (id, el) in self.inner.ids.iter().zip(self.inner.data.iter()) {
+            debug_string.field(&id.to_string(), el);
+        }
+        debug_string.finish()
+    }
+}
+
+impl<T> OrderedStableIndexVec<T> {
+    /// Creates a new empty OrderedStableIndexVec
+    pub fn new() -> Self {
+        Self {
+            inner: StableIndexVec::new(),
+        }
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Gets an optional reference to an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Adds an element to the end of the container and returns its key
+    pub fn add(&mut self, el: T) -> SIVKey {
+        self.inner.add(el)
+    }
+
+    /// Removes an element from the container by its key, keeping the order of the remaining elements. This is O(n)
+    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+        self.inner.remove_stable(key)
+    }
+
+    /// Gets the oldest element still in the container
+    pub fn first(&self) -> Option<(SIVKey, &T)> {
+        self.inner.first()
+    }
+
+    /// Gets the newest element in the container
+    pub fn last(&self) -> Option<(SIVKey, &T)> {
+        self.inner.last()
+    }
+
+    /// Returns an iterator over all key-value pairs in insertion order
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.inner.iter()
+    }
+
+    /// Returns an iterator over the valid keys in insertion order
+    pub fn keys(&self) -> impl Iterator<Item = SIVKey> + '_ {
+        self.inner.keys()
+    }
+
+    /// Returns an iterator over the values in insertion order
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.inner.values()
+    }
+}
+
+impl<T> OrderedStableIndexVec<T> where T: PartialEq {
+    /// Checks if the given element exists in the container
+    pub fn contains(&self, el: &T) -> bool {
+        self.inner.contains(el)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iter_is_in_insertion_order_after_removals() {
+        let mut container = OrderedStableIndexVec::new();
+        let keys: Vec<_> = (0..10).map(|i| container.add(i)).collect();
+
+        container.remove(keys[0]);
+        container.remove(keys[4]);
+        container.remove(keys[9]);
+        let key10 = container.add(10);
+
+        let values: Vec<_> = container.values().copied().collect();
+        assert_eq!(values, [1, 2, 3, 5, 6, 7, 8, 10]);
+
+        let expected_keys = [keys[1], keys[2], keys[3], keys[5], keys[6], keys[7], keys[8], key10];
+        assert_eq!(container.keys().collect::<Vec<_>>(), expected_keys);
+    }
+
+    #[test]
+    fn removed_keys_are_stale() {
+        let mut container = OrderedStableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+
+        assert_eq!(container.remove(key1), Some(1));
+        assert_eq!(container.remove(key1), None);
+        assert_eq!(container.get(key1), None);
+        assert_eq!(container.get(key2), Some(&2));
+        assert_eq!(container.len(), 1);
+    }
+
+    #[test]
+    fn first_and_last_follow_insertion_order() {
+        let mut container = OrderedStableIndexVec::new();
+        let key1 = container.add(1);
+        let key2 = container.add(2);
+        let key3 = container.add(3);
+
+        container.remove(key1);
+        assert_eq!(container.first(), Some((key2, &2)));
+        assert_eq!(container.last(), Some((key3, &3)));
+    }
+}
@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+
+use crate::{SIVKey, StableIndexVec};
+
+/// A StableIndexVec that keeps its elements sorted by a key computed from each element, for leaderboards and
+/// priority-ordered pools.
+///
+/// Next to the base container it keeps the sort keys in a sorted vector, so `add` and `remove` are O(log n) to
+/// search plus O(n) to shift. Elements with equal sort keys stay in insertion order. Values can't be mutated in
+/// place, since that could change their sort key; remove and re-add them instead.
+#[derive(Clone)]
+pub struct OrderedBy<T, K: Ord, F: Fn(&T) -> K = fn(&T) -> K> {
+    inner: StableIndexVec<T>,
+    /// The sort key and container key of every element, ascending by sort key
+    sorted: Vec<(K, SIVKey)>,
+    key_fn: F,
+}
+
+/// Formats the elements and sort keys. The key function has no Debug output, so it's left out
+impl<T: core::fmt::Debug, K: Ord + core::fmt::Debug, F: Fn(&T) -> K> core::fmt::Debug for OrderedBy<T, K, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OrderedBy")
+            .field("inner", &self.inner)
+            .field("sorted", &self.sorted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> OrderedBy<T, K, F> {
+    /// Creates a new empty container sorted by `key_fn`, which may capture state such as a weight table
+    pub fn new(key_fn: F) -> Self {
+        Self {
+            inner: StableIndexVec::new(),
+            sorted: Vec::new(),
+            key_fn,
+        }
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Gets an optional reference to an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Adds an element in its sorted position, after any elements with an equal sort key, and returns its key
+    pub fn add(&mut self, el: T) -> SIVKey {
+        let sort_key = (self.key_fn)(&el);
+        let position = self.sorted.partition_point(|(k, _)| *k <= sort_key);
+        let key = self.inner.add(el);
+        self.sorted.insert(position, (sort_key, key));
+        key
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&mut self, key: SIVKey) -> Option<T> {
+        let el = self.inner.remove(key)?;
+        let sort_key = (self.key_fn)(&el);
+        let start = self.sorted.partition_point(|(k, _)| *k < sort_key);
+        let offset = self.sorted[start..].iter().position(|&(_, k)| k == key);
+        if let Some(offset) = offset {
+            self.sorted.remove(start + offset);
+        }
+        Some(el)
+    }
+
+    /// Gets the element at position `rank` in sorted order
+    pub fn nth(&self, rank: usize) -> Option<(SIVKey, &T)> {
+        let &(_, key) = self.sorted.get(rank)?;
+        Some((key, self.inner.get(key)?))
+    }
+
+    /// Binary searches the sort keys for `sort_key`, like `slice::binary_search_by_key`. Returns `Ok` with the rank
+    /// of a matching element, which `nth` resolves, or `Err` with the rank where such an element would be added
+    pub fn binary_search_by_key(&self, sort_key: &K) -> Result<usize, usize> {
+        self.sorted.binary_search_by(|(k, _)| k.cmp(sort_key))
+    }
+
+    /// Returns an iterator over all key-value pairs in ascending sort key order
+    pub fn iter(&self) -> impl Iterator<Item = (SIVKey, &T)> + '_ {
+        self.sorted.iter().filter_map(|&(_, key)| Some((key, self.inner.get(key)?)))
+    }
+
+    /// Returns an iterator over the valid keys in ascending sort key order
+    pub fn keys(&self) -> impl Iterator<Item = SIVKey> + '_ {
+        self.sorted.iter().map(|&(_, key)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_in_sort_key_order() {
+        let mut board = OrderedBy::new(|&(_, score): &(&str, u32)| core::cmp::Reverse(score));
+        let alice = board.add(("alice", 30));
+        board.add(("bob", 50));
+        let carol = board.add(("carol", 30));
+        board.add(("dave", 10));
+
+        let names: Vec<_> = board.iter().map(|(_, &(name, _))| name).collect();
+        assert_eq!(names, ["bob", "alice", "carol", "dave"]);
+
+        assert_eq!(board.remove(alice), Some(("alice", 30)));
+        assert_eq!(board.keys().nth(1), Some(carol));
+        assert_eq!(board.len(), 3);
+        assert_eq!(board.remove(alice), None);
+    }
+
+    #[test]
+    fn binary_search_finds_ranks() {
+        let mut pool = OrderedBy::new(|el: &i32| *el);
+        for el in [40, 10, 30, 20] {
+            pool.add(el);
+        }
+
+        let rank = pool.binary_search_by_key(&30).unwrap();
+        assert_eq!(pool.nth(rank).map(|(_, el)| *el), Some(30));
+        assert_eq!(pool.binary_search_by_key(&25), Err(2));
+        assert_eq!(pool.binary_search_by_key(&50), Err(4));
+        assert_eq!(pool.nth(4), None);
+    }
+
+    #[test]
+    fn sorts_by_a_capturing_closure() {
+        let weights = std::collections::HashMap::from([("sword", 3), ("shield", 5), ("potion", 1)]);
+        let mut pool = OrderedBy::new(|item: &&str| weights[item]);
+        for item in ["shield", "potion", "sword"] {
+            pool.add(item);
+        }
+
+        assert_eq!(pool.iter().map(|(_, &item)| item).collect::<Vec<_>>(), ["potion", "sword", "shield"]);
+        assert_eq!(pool.binary_search_by_key(&5), Ok(2));
+        assert!(format!("{pool:?}").starts_with("OrderedBy"));
+    }
+}
@@ -0,0 +1,33 @@
+use allocator_api2::alloc::Allocator;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{IndexInt, SIVKey, StableIndexVec};
+
+impl<T: Send, I: IndexInt, A: Allocator> StableIndexVec<T, I, A> {
+    /// Adds every value from a parallel iterator and returns their keys in the iterator's order.
+    /// The values are produced in parallel and buffered, then added serially, so this pays off when building each
+    /// value is expensive; the adds themselves are no faster than `add_many`
+    pub fn par_extend<P: IntoParallelIterator<Item = T>>(&mut self, items: P) -> alloc::vec::Vec<SIVKey<I>> {
+        let items: alloc::vec::Vec<T> = items.into_par_iter().collect();
+        self.add_many(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn par_extend_keys_follow_input_order() {
+        let mut container = StableIndexVec::new();
+        let existing = container.add(String::from("existing"));
+        container.remove(existing);
+
+        let keys = container.par_extend((0..1000).into_par_iter().map(|i| i.to_string()));
+        assert_eq!(keys.len(), 1000);
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(container.get(key), Some(&i.to_string()));
+        }
+        assert!(!keys.contains(&existing));
+    }
+}
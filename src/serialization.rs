@@ -0,0 +1,186 @@
+use allocator_api2::alloc::Allocator;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{IndexInt, StableIndexVec};
+
+/// Version of the serialized layout written by this build. Input without a version is read as version 1,
+/// the layout from before versioning; newer versions are rejected rather than misread
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Borrowed view of every field that has to survive a round trip, in serialized form
+#[derive(Serialize)]
+#[serde(rename = "StableIndexVec")]
+struct ReprRef<'a, T, I> {
+    version: u32,
+    index: &'a [I],
+    generations: &'a [I],
+    ids: &'a [I],
+    data: &'a [T],
+    generation_floor: I,
+}
+
+/// Owned counterpart of `ReprRef`, checked with `validate` before it becomes a container.
+/// The other fields are optional so an unknown version is reported as such, instead of as whichever field it lacks
+#[derive(Deserialize)]
+#[serde(rename = "StableIndexVec")]
+struct Repr<T, I> {
+    #[serde(default = "first_version")]
+    version: u32,
+    index: Option<alloc::vec::Vec<I>>,
+    generations: Option<alloc::vec::Vec<I>>,
+    ids: Option<alloc::vec::Vec<I>>,
+    data: Option<alloc::vec::Vec<T>>,
+    generation_floor: Option<I>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+fn required<T, E: serde::de::Error>(field: Option<T>, name: &'static str) -> Result<T, E> {
+    field.ok_or_else(|| E::missing_field(name))
+}
+
+/// Serializes the full internal state, so every key (live or stale) means the same thing after a round trip.
+/// The auto-compaction setting and any uncollected compaction remap are not included
+impl<T: Serialize, I: IndexInt + Serialize, A: Allocator> Serialize for StableIndexVec<T, I, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ReprRef {
+            version: FORMAT_VERSION,
+            index: &self.index,
+            generations: &self.generations,
+            ids: &self.ids,
+            data: &self.data,
+            generation_floor: self.generation_floor,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Rejects input that breaks any of the container's invariants (see `validate`) with an error describing the
+/// first problem, so a corrupted file can't produce a container that panics or returns wrong values later
+impl<'de, T, I, A> Deserialize<'de> for StableIndexVec<T, I, A>
+where
+    T: Deserialize<'de>,
+    I: IndexInt + Deserialize<'de>,
+    A: Allocator + Clone + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = Repr::<T, I>::deserialize(deserializer)?;
+        if repr.version != FORMAT_VERSION {
+            return Err(D::Error::custom(alloc::format!(
+                "unsupported StableIndexVec format version {} (this build reads version {FORMAT_VERSION})",
+                repr.version
+            )));
+        }
+
+        let mut container = Self::default();
+        container.index.extend(required::<_, D::Error>(repr.index, "index")?);
+        container.generations.extend(required::<_, D::Error>(repr.generations, "generations")?);
+        container.ids.extend(required::<_, D::Error>(repr.ids, "ids")?);
+        container.data.extend(required::<_, D::Error>(repr.data, "data")?);
+        container.generation_floor = required::<_, D::Error>(repr.generation_floor, "generation_floor")?;
+
+        container
+            .validate()
+            .map_err(|err| D::Error::custom(alloc::format!("invalid StableIndexVec: {err}")))?;
+        Ok(container)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<T, I, A> StableIndexVec<T, I, A>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+    I: IndexInt + Serialize + serde::de::DeserializeOwned,
+    A: Allocator + Clone + Default,
+{
+    /// Serializes the container to a JSON string, keeping every key valid once it's loaded with `from_json`
+    pub fn to_json(&self) -> serde_json::Result<alloc::string::String> {
+        serde_json::to_string(self)
+    }
+
+    /// Loads a container from JSON written by `to_json`, rejecting input that breaks the container's invariants
+    pub fn from_json(s: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_preserves_keys() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(["a", "b", "c"].map(String::from));
+        container.remove(keys[1]);
+
+        let json = serde_json::to_string(&container).unwrap();
+        let mut loaded: StableIndexVec<String> = serde_json::from_str(&json).unwrap();
+        assert!(loaded == container);
+        assert_eq!(loaded.get(keys[1]), None);
+        assert_ne!(loaded.add("d".to_string()), keys[1]);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_helpers_round_trip() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many([1, 2, 3]);
+        container.remove(keys[0]);
+
+        let loaded = StableIndexVec::<i32>::from_json(&container.to_json().unwrap()).unwrap();
+        assert!(loaded == container);
+        assert_eq!(loaded.get(keys[2]), Some(&3));
+        assert!(StableIndexVec::<i32>::from_json("[1, 2]").is_err());
+    }
+
+    fn load(json: &str) -> String {
+        serde_json::from_str::<StableIndexVec<i32>>(json).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn rejects_more_data_than_ids() {
+        let err = load(r#"{"index":[0],"generations":[0],"ids":[0],"data":[1,2],"generation_floor":0}"#);
+        assert!(err.contains("data.len() (2) is greater than ids.len() (1)"), "{err}");
+    }
+
+    #[test]
+    fn rejects_out_of_range_ids() {
+        let err = load(r#"{"index":[0],"generations":[0],"ids":[5],"data":[1],"generation_floor":0}"#);
+        assert!(err.contains("ids[0] (5) is out of range"), "{err}");
+    }
+
+    #[test]
+    fn rejects_broken_index_round_trip() {
+        let err = load(r#"{"index":[1,0],"generations":[0,0],"ids":[0,1],"data":[1,2],"generation_floor":0}"#);
+        assert!(err.contains("does not point back"), "{err}");
+
+        let err = load(r#"{"index":[0,1],"generations":[0,0],"ids":[0,0],"data":[1,2],"generation_floor":0}"#);
+        assert!(err.contains("appears more than once"), "{err}");
+    }
+
+    #[test]
+    fn rejects_mismatched_generations() {
+        let err = load(r#"{"index":[0,1],"generations":[0],"ids":[0,1],"data":[1,2],"generation_floor":0}"#);
+        assert!(err.contains("generations.len() (1) does not match index.len() (2)"), "{err}");
+    }
+
+    #[test]
+    fn writes_and_checks_the_format_version() {
+        let json = serde_json::to_string(&StableIndexVec::<i32>::new()).unwrap();
+        assert!(json.starts_with(r#"{"version":1,"#), "{json}");
+
+        let unversioned = r#"{"index":[0],"generations":[0],"ids":[0],"data":[7],"generation_floor":0}"#;
+        let loaded: StableIndexVec<i32> = serde_json::from_str(unversioned).unwrap();
+        assert_eq!(loaded.values_slice(), [7]);
+
+        let err = load(r#"{"version":2,"slots":[]}"#);
+        assert!(err.contains("unsupported StableIndexVec format version 2"), "{err}");
+
+        let err = load(r#"{"version":1,"index":[],"generations":[],"ids":[]}"#);
+        assert!(err.contains("missing field `data`"), "{err}");
+    }
+}
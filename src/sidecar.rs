@@ -0,0 +1,179 @@
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec;
+
+use crate::{IndexInt, SIVKey, StableIndexVec};
+
+/// Metadata stored alongside a container's elements, keyed by the same `SIVKey`s.
+///
+/// Entries are stored per id, so lookups are O(1) and an entry for an old generation of an id never
+/// matches a newer key. Call `prune` with the parent container to drop the entries of removed elements,
+/// instead of leaking them like a separate `HashMap<SIVKey, M>` would.
+#[derive(Clone, Debug)]
+pub struct SidecarMap<M, I: IndexInt = usize> {
+    entries: Vec<Option<(I, M)>>,
+    len: usize,
+}
+
+impl<M, I: IndexInt> Default for SidecarMap<M, I> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<M, I: IndexInt> SidecarMap<M, I> {
+    /// Creates a new empty SidecarMap
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets an optional reference to the metadata for a key
+    pub fn get(&self, key: SIVKey<I>) -> Option<&M> {
+        match self.entries.get(key.id.to_usize())? {
+            Some((generation, meta)) if *generation == key.generation => Some(meta),
+            _ => None,
+        }
+    }
+
+    /// Gets an optional mutable reference to the metadata for a key
+    pub fn get_mut(&mut self, key: SIVKey<I>) -> Option<&mut M> {
+        match self.entries.get_mut(key.id.to_usize())? {
+            Some((generation, meta)) if *generation == key.generation => Some(meta),
+            _ => None,
+        }
+    }
+
+    /// Sets the metadata for a key, returning the previous metadata for that same key.
+    /// An entry left over from an older generation of the key's id is replaced. The entries grow to the key's id,
+    /// so this panics instead of aborting if that many entries can't be allocated
+    pub fn insert(&mut self, key: SIVKey<I>, meta: M) -> Option<M> {
+        let id = key.id.to_usize();
+        if id >= self.entries.len() {
+            let len = id.checked_add(1).filter(|len| self.entries.try_reserve(len - self.entries.len()).is_ok());
+            let Some(len) = len else {
+                panic!("SidecarMap can't allocate entries up to id {id}");
+            };
+            self.entries.resize_with(len, || None);
+        }
+
+        let previous = self.entries[id].replace((key.generation, meta));
+        match previous {
+            Some((generation, meta)) if generation == key.generation => Some(meta),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Removes the metadata for a key
+    pub fn remove(&mut self, key: SIVKey<I>) -> Option<M> {
+        let entry = self.entries.get_mut(key.id.to_usize())?;
+        if entry.as_ref().is_none_or(|(generation, _)| *generation != key.generation) {
+            return None;
+        }
+        self.len -= 1;
+        entry.take().map(|(_, meta)| meta)
+    }
+
+    /// Drops every entry whose key isn't live in `container`, returning how many were dropped
+    pub fn prune<T, A: Allocator>(&mut self, container: &StableIndexVec<T, I, A>) -> usize {
+        let mut pruned = 0;
+        for (id, entry) in self.entries.iter_mut().enumerate() {
+            if let Some((generation, _)) = entry
+                && !container.contains_key(SIVKey::new(I::from_usize(id), *generation))
+            {
+                *entry = None;
+                pruned += 1;
+            }
+        }
+        self.len -= pruned;
+        pruned
+    }
+
+    /// Returns an iterator over every key and its metadata, in id order
+    pub fn iter(&self) -> impl Iterator<Item = (SIVKey<I>, &M)> + '_ {
+        self.entries.iter().enumerate().filter_map(|(id, entry)| {
+            let (generation, meta) = entry.as_ref()?;
+            Some((SIVKey::new(I::from_usize(id), *generation), meta))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_container_keys() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(["a", "b", "c"]);
+        let mut meta = SidecarMap::new();
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(meta.insert(key, i), None);
+        }
+
+        assert_eq!(meta.insert(keys[1], 10), Some(1));
+        assert_eq!(meta.get(keys[1]), Some(&10));
+        *meta.get_mut(keys[2]).unwrap() += 1;
+        assert_eq!(meta.remove(keys[2]), Some(3));
+        assert_eq!(meta.remove(keys[2]), None);
+        assert_eq!(meta.len(), 2);
+    }
+
+    #[test]
+    fn prune_drops_removed_keys() {
+        let mut container = StableIndexVec::new();
+        let keys = container.add_many(0..4);
+        let mut meta = SidecarMap::new();
+        for &key in &keys {
+            meta.insert(key, "meta");
+        }
+
+        container.remove(keys[0]);
+        container.remove(keys[3]);
+        let reused = container.add(4);
+        assert_eq!(meta.get(reused), None);
+
+        assert_eq!(meta.prune(&container), 2);
+        assert_eq!(meta.len(), 2);
+        assert_eq!(meta.iter().map(|(key, _)| key).collect::<Vec<_>>(), [keys[1], keys[2]]);
+        assert_eq!(meta.prune(&container), 0);
+    }
+
+    #[test]
+    fn insert_replaces_older_generations() {
+        let mut meta = SidecarMap::<&str>::new();
+        meta.insert(SIVKey::new(0, 0), "old");
+        assert_eq!(meta.insert(SIVKey::new(0, 1), "new"), None);
+        assert_eq!(meta.get(SIVKey::new(0, 0)), None);
+        assert_eq!(meta.get(SIVKey::new(0, 1)), Some(&"new"));
+        assert_eq!(meta.len(), 1);
+    }
+
+    #[test]
+    fn insert_with_a_huge_id_panics_cleanly() {
+        let mut meta = SidecarMap::new();
+        let key = SIVKey::new(1, 0);
+        meta.insert(key, "kept");
+        for id in [usize::MAX, usize::MAX / 2] {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| meta.insert(SIVKey::new(id, 0), "huge")));
+            assert!(result.is_err());
+        }
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta.iter().collect::<Vec<_>>(), [(key, &"kept")]);
+    }
+}
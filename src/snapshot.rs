@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use arc_swap::ArcSwap;
+
+use crate::{SIVKey, StableIndexVec};
+
+/// A StableIndexVec shared through atomically swapped snapshots, for read-mostly data where readers must never block.
+///
+/// `load` hands out an `Arc` of the current version without locking, and that snapshot never changes under the
+/// reader. Writers clone the current version, modify the clone and swap it in, so every write costs a full clone.
+/// Writers are serialized with each other, so no update is lost, but they never wait for readers.
+#[derive(Debug)]
+pub struct ArcSwapStableIndexVec<T> {
+    current: ArcSwap<StableIndexVec<T>>,
+    writer: Mutex<()>,
+}
+
+impl<T> Default for ArcSwapStableIndexVec<T> {
+    fn default() -> Self {
+        Self::from(StableIndexVec::new())
+    }
+}
+
+impl<T> From<StableIndexVec<T>> for ArcSwapStableIndexVec<T> {
+    fn from(container: StableIndexVec<T>) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(container),
+            writer: Mutex::new(()),
+        }
+    }
+}
+
+impl<T> ArcSwapStableIndexVec<T> {
+    /// Creates a new empty ArcSwapStableIndexVec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a snapshot of the current version. This never blocks, and later writes don't affect it
+    pub fn load(&self) -> Arc<StableIndexVec<T>> {
+        self.current.load_full()
+    }
+
+    /// Replaces the whole container with `container`
+    pub fn store(&self, container: StableIndexVec<T>) {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.current.store(Arc::new(container));
+    }
+}
+
+impl<T: Clone> ArcSwapStableIndexVec<T> {
+    /// Runs `f` on a clone of the current version and publishes the result. Readers keep seeing the old version
+    /// until `f` returns, and a panic in `f` publishes nothing
+    pub fn update<R>(&self, f: impl FnOnce(&mut StableIndexVec<T>) -> R) -> R {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut next = StableIndexVec::clone(&self.current.load());
+        let result = f(&mut next);
+        self.current.store(Arc::new(next));
+        result
+    }
+
+    /// Adds an element in a new version and returns its key
+    pub fn add(&self, el: T) -> SIVKey {
+        self.update(|container| container.add(el))
+    }
+
+    /// Removes an element by its key in a new version. Snapshots taken earlier still hold it
+    pub fn remove(&self, key: SIVKey) -> Option<T> {
+        self.update(|container| container.remove(key))
+    }
+
+    /// Gets a clone of an element by its key from the current version
+    pub fn get_cloned(&self, key: SIVKey) -> Option<T> {
+        self.current.load().get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_are_unaffected_by_writes() {
+        let shared = ArcSwapStableIndexVec::new();
+        let a = shared.add("a");
+        let before = shared.load();
+
+        assert_eq!(shared.remove(a), Some("a"));
+        let b = shared.add("b");
+        assert_eq!(before.get(a), Some(&"a"));
+        assert_eq!(before.get(b), None);
+        assert_eq!(shared.get_cloned(b), Some("b"));
+        assert_eq!(shared.load().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        let shared = ArcSwapStableIndexVec::new();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        let snapshot = shared.load();
+                        assert!(snapshot.validate().is_ok());
+                        assert!(snapshot.values().all(|&el| el % 2 == 0));
+                    }
+                });
+            }
+            for writer in 0..2 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for i in 0..100 {
+                        let key = shared.add(writer * 1000 + i * 2);
+                        if i % 2 == 1 {
+                            shared.remove(key);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.load().len(), 100);
+    }
+}
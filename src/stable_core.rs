@@ -0,0 +1,226 @@
+/// Implements the core key/generation bookkeeping shared by every container type.
+///
+/// The container must have `index`, `generations`, `ids` and `data` fields whose types
+/// support the `Vec` methods used here (`Vec`, `SmallVec`, ...). If `after_remove = method` is given,
+/// `remove` calls `self.method()` once the element has been taken out. If `add = name` is given, the panicking
+/// add is generated as a private method called `name` instead, for containers whose public `add` can fail.
+macro_rules! impl_stable_core {
+    (impl [$($params:tt)*] $container:ty, add = $add:ident $(, after_remove = $after_remove:ident)?) => {
+        impl_stable_core!(@core [$($params)*] $container, [] $add $(, $after_remove)?);
+    };
+    (impl [$($params:tt)*] $container:ty $(, after_remove = $after_remove:ident)?) => {
+        impl_stable_core!(@core [$($params)*] $container, [pub] add $(, $after_remove)?);
+    };
+    (@core [$($params:tt)*] $container:ty, [$($add_vis:tt)*] $add:ident $(, $after_remove:ident)?) => {
+        impl<$($params)*> $container {
+            fn data_index(&self, key: $crate::SIVKey<I>) -> Option<usize> {
+                let id = key.id.to_usize();
+                let data_index = self.index.get(id)?.to_usize();
+                match self.generations.get(id) {
+                    Some(generation) if *generation == key.generation && data_index < self.data.len() => Some(data_index),
+                    _ => None,
+                }
+            }
+
+            /// Gets the length of the data vector
+            pub fn len(&self) -> usize {
+                self.data.len()
+            }
+
+            /// Gets the number of ids the container has allocated metadata for, live or free.
+            /// Since removed ids are recycled, this stays bounded by the peak number of live elements
+            pub fn slot_count(&self) -> usize {
+                self.index.len()
+            }
+
+            /// Checks if the container has no elements
+            pub fn is_empty(&self) -> bool {
+                self.data.is_empty()
+            }
+
+            /// Gets an optional reference to an element by its key
+            pub fn get(&self, key: $crate::SIVKey<I>) -> Option<&T> {
+                let data_index = self.data_index(key)?;
+                self.data.get(data_index)
+            }
+
+            /// Gets an optional mutable reference to an element by its key
+            pub fn get_mut(&mut self, key: $crate::SIVKey<I>) -> Option<&mut T> {
+                let data_index = self.data_index(key)?;
+                self.data.get_mut(data_index)
+            }
+
+            /// Checks if the key refers to a live element. This is O(1)
+            pub fn contains_key(&self, key: $crate::SIVKey<I>) -> bool {
+                self.data_index(key).is_some()
+            }
+
+            /// Adds an element to the container and returns its key.
+            /// Panics if every id representable by `I` (except `I::MAX`, the retired sentinel) is in use or retired
+            $($add_vis)* fn $add(&mut self, el: T) -> $crate::SIVKey<I> {
+                let data_len = self.data.len();
+                assert!(data_len <= self.ids.len(), "data.len() cannot be greater than ids.len()");
+
+                // reserve everything before pushing anything, so a failed allocation can't leave
+                // the vectors with different lengths
+                let new_id = data_len == self.ids.len();
+                if new_id {
+                    assert!(self.index.len() < I::MAX.to_usize(), "StableIndexVec ran out of ids");
+                    self.index.reserve(1);
+                    self.generations.reserve(1);
+                    self.ids.reserve(1);
+                }
+                self.data.reserve(1);
+
+                if new_id {
+                    let id = self.index.len();
+                    self.index.push(I::from_usize(data_len));
+                    self.generations.push(self.generation_floor);
+                    self.ids.push(I::from_usize(id));
+                }
+
+                self.data.push(el);
+
+                let id = self.ids[data_len];
+                $crate::SIVKey {
+                    id,
+                    generation: self.generations[id.to_usize()],
+                }
+            }
+
+            /// Removes an element from the container by its key
+            pub fn remove(&mut self, key: $crate::SIVKey<I>) -> Option<T> {
+                let el = self.swap_remove_key(key)?;
+                $(self.$after_remove();)?
+                Some(el)
+            }
+
+            /// Removes an element by swapping the last element into its place, without running the `after_remove` hook
+            fn swap_remove_key(&mut self, key: $crate::SIVKey<I>) -> Option<T> {
+                let data_index = self.data_index(key)?;
+
+                let last_index = self.data.len() - 1;
+                if data_index < last_index {
+                    self.data.swap(data_index, last_index);
+                    self.ids.swap(data_index, last_index);
+                    self.index[self.ids[data_index].to_usize()] = I::from_usize(data_index);
+                    self.index[self.ids[last_index].to_usize()] = I::from_usize(last_index);
+                }
+
+                // the bookkeeping is finished before the value is moved out, so even if the caller's drop of it
+                // panics, the container has already reached a consistent state
+                self.release_id(key.id, last_index);
+                self.data.pop()
+            }
+
+            /// Removes an element from the container by its key, shifting later elements down so the
+            /// remaining elements keep their relative order. This is O(n), prefer `remove` when order doesn't matter
+            pub fn remove_stable(&mut self, key: $crate::SIVKey<I>) -> Option<T> {
+                let data_index = self.data_index(key)?;
+                let el = self.data.remove(data_index);
+
+                let free_slot = self.data.len();
+                let id = self.ids.remove(data_index);
+                self.ids.insert(free_slot, id);
+                for slot in data_index..=free_slot {
+                    self.index[self.ids[slot].to_usize()] = I::from_usize(slot);
+                }
+
+                self.release_id(id, free_slot);
+                Some(el)
+            }
+
+            /// Bumps the generation of an id that was just moved to the free `slot`, retiring it if the counter is exhausted
+            fn release_id(&mut self, id: I, slot: usize) {
+                let generation = &mut self.generations[id.to_usize()];
+                match generation.checked_increment() {
+                    Some(next) => *generation = next,
+                    None => self.retire_free_slot(slot),
+                }
+            }
+
+            /// Permanently retires the free id stored at `slot` because its generation can't be advanced.
+            /// A retired id's `index` entry holds `I::MAX`, and it is never handed out again so its old keys can never resolve
+            fn retire_free_slot(&mut self, slot: usize) {
+                let retired_id = self.ids.swap_remove(slot);
+                self.index[retired_id.to_usize()] = I::MAX;
+                if let Some(&moved_id) = self.ids.get(slot) {
+                    self.index[moved_id.to_usize()] = I::from_usize(slot);
+                }
+            }
+
+            /// Checks all of the container's internal invariants, returning a description of the first broken one
+            pub fn validate(&self) -> Result<(), alloc::string::String> {
+                if self.data.len() > self.ids.len() {
+                    return Err(alloc::format!("data.len() ({}) is greater than ids.len() ({})", self.data.len(), self.ids.len()));
+                }
+                if self.ids.len() > self.index.len() {
+                    return Err(alloc::format!("ids.len() ({}) is greater than index.len() ({})", self.ids.len(), self.index.len()));
+                }
+                if self.generations.len() != self.index.len() {
+                    return Err(alloc::format!(
+                        "generations.len() ({}) does not match index.len() ({})",
+                        self.generations.len(),
+                        self.index.len()
+                    ));
+                }
+
+                let mut seen = alloc::vec![false; self.index.len()];
+                for (slot, &id) in self.ids.iter().enumerate() {
+                    let id = id.to_usize();
+                    if id >= self.index.len() {
+                        return Err(alloc::format!("ids[{slot}] ({id}) is out of range of index"));
+                    }
+                    if seen[id] {
+                        return Err(alloc::format!("id {id} appears more than once in ids"));
+                    }
+                    seen[id] = true;
+                    if self.index[id].to_usize() != slot {
+                        return Err(alloc::format!("index[{id}] ({}) does not point back to slot {slot}", self.index[id]));
+                    }
+                }
+
+                for (id, &slot) in self.index.iter().enumerate() {
+                    if seen[id] {
+                        continue;
+                    }
+                    if slot != I::MAX {
+                        return Err(alloc::format!("index[{id}] ({slot}) is not in ids but is not retired"));
+                    }
+                    if self.generations[id] != I::MAX {
+                        return Err(alloc::format!("id {id} is retired but its generation ({}) is not exhausted", self.generations[id]));
+                    }
+                }
+
+                Ok(())
+            }
+
+            /// Returns an iterator over all key-value pairs in the container. The iterator element type is (SIVKey, &'a T)
+            pub fn iter(&self) -> $crate::Iter<'_, T, I> {
+                $crate::Iter {
+                    ids: &self.ids,
+                    generations: &self.generations,
+                    data: &self.data,
+                    position: 0,
+                }
+            }
+
+            /// Returns an iterator over the valid keys in the container
+            pub fn keys(&self) -> impl Iterator<Item = $crate::SIVKey<I>> + '_ {
+                self.iter().map(|(key, _)| key)
+            }
+
+            /// Returns an iterator over the values in the container
+            pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+                self.iter().map(|(_, value)| value)
+            }
+        }
+
+        impl<$($params)*> $container where T: PartialEq {
+            /// Checks if the given element exists in the container
+            pub fn contains(&self, el: &T) -> bool {
+                self.data.contains(el)
+            }
+        }
+    };
+}
@@ -0,0 +1,356 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{SIVKey, StableIndexVec};
+
+/// A StableIndexVec behind a `Mutex`, for sharing one arena between threads.
+///
+/// Every method takes the lock for its whole duration, so each call is atomic with respect to the others; use
+/// `with` to make several operations atomic together. `get` clones the value out, since no reference can outlive
+/// the lock. A panic while the lock is held doesn't poison the container for other threads: the container's
+/// operations leave it consistent even when they unwind, so the lock is simply taken over.
+#[derive(Default, Debug)]
+pub struct SyncStableIndexVec<T> {
+    inner: Mutex<StableIndexVec<T>>,
+}
+
+impl<T> From<StableIndexVec<T>> for SyncStableIndexVec<T> {
+    fn from(container: StableIndexVec<T>) -> Self {
+        Self {
+            inner: Mutex::new(container),
+        }
+    }
+}
+
+impl<T> SyncStableIndexVec<T> {
+    /// Creates a new empty SyncStableIndexVec
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(StableIndexVec::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, StableIndexVec<T>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.lock().contains_key(key)
+    }
+
+    /// Adds an element to the container and returns its key
+    pub fn add(&self, el: T) -> SIVKey {
+        self.lock().add(el)
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&self, key: SIVKey) -> Option<T> {
+        self.lock().remove(key)
+    }
+
+    /// Runs `f` with the lock held, so everything it does to the container happens atomically
+    pub fn with<R>(&self, f: impl FnOnce(&mut StableIndexVec<T>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Consumes the wrapper, returning the container
+    pub fn into_inner(self) -> StableIndexVec<T> {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Clone> SyncStableIndexVec<T> {
+    /// Gets a clone of an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<T> {
+        self.lock().get(key).cloned()
+    }
+}
+
+/// A StableIndexVec behind an `RwLock`, for read-heavy sharing where many threads look elements up and few change them.
+///
+/// Any number of `read` guards can be held at once, while a `write` guard waits for them and excludes everyone
+/// else. The convenience methods each hold a guard only for their own call. Poisoning is handled like
+/// `SyncStableIndexVec`: a panic under the lock doesn't lock other threads out.
+#[derive(Default, Debug)]
+pub struct SharedStableIndexVec<T> {
+    inner: RwLock<StableIndexVec<T>>,
+}
+
+impl<T> From<StableIndexVec<T>> for SharedStableIndexVec<T> {
+    fn from(container: StableIndexVec<T>) -> Self {
+        Self {
+            inner: RwLock::new(container),
+        }
+    }
+}
+
+impl<T> SharedStableIndexVec<T> {
+    /// Creates a new empty SharedStableIndexVec
+    pub const fn new() -> Self {
+        Self {
+            inner: RwLock::new(StableIndexVec::new()),
+        }
+    }
+
+    /// Takes shared read access to the container, waiting for any writer to finish
+    pub fn read(&self) -> RwLockReadGuard<'_, StableIndexVec<T>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes exclusive write access to the container, waiting for all readers and writers to finish
+    pub fn write(&self) -> RwLockWriteGuard<'_, StableIndexVec<T>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the number of elements in the container
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Checks if the container has no elements
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        self.read().contains_key(key)
+    }
+
+    /// Adds an element to the container and returns its key
+    pub fn add(&self, el: T) -> SIVKey {
+        self.write().add(el)
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&self, key: SIVKey) -> Option<T> {
+        self.write().remove(key)
+    }
+
+    /// Consumes the wrapper, returning the container
+    pub fn into_inner(self) -> StableIndexVec<T> {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Clone> SharedStableIndexVec<T> {
+    /// Gets a clone of an element by its key
+    pub fn get_cloned(&self, key: SIVKey) -> Option<T> {
+        self.read().get(key).cloned()
+    }
+}
+
+/// A StableIndexVec split into independently locked shards, so many threads can add at once without contending.
+///
+/// Adds are spread over the shards round-robin. A key's id encodes its shard (`id % shard_count`) alongside the
+/// id within that shard, so `get` and `remove` lock only the shard holding the element. Operations are atomic
+/// per shard, not across the whole container, so `len` may be stale by the time it returns.
+#[derive(Debug)]
+pub struct ShardedStableIndexVec<T> {
+    shards: Vec<Mutex<StableIndexVec<T>>>,
+    next_shard: AtomicUsize,
+}
+
+impl<T> ShardedStableIndexVec<T> {
+    /// Creates a new empty container with `shard_count` shards. Panics if `shard_count` is 0
+    pub fn with_shards(shard_count: usize) -> Self {
+        assert!(shard_count > 0, "ShardedStableIndexVec needs at least one shard");
+        Self {
+            shards: (0..shard_count).map(|_| Mutex::default()).collect(),
+            next_shard: AtomicUsize::new(0),
+        }
+    }
+
+    /// Gets the number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn lock(&self, shard: usize) -> MutexGuard<'_, StableIndexVec<T>> {
+        self.shards[shard].lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Splits a key into its shard and the key within that shard
+    fn route(&self, key: SIVKey) -> (usize, SIVKey) {
+        let shard_count = self.shards.len();
+        (key.id % shard_count, SIVKey::new(key.id / shard_count, key.generation))
+    }
+
+    /// Gets the number of elements across all shards, locking each in turn
+    pub fn len(&self) -> usize {
+        (0..self.shards.len()).map(|shard| self.lock(shard).len()).sum()
+    }
+
+    /// Checks if every shard is empty
+    pub fn is_empty(&self) -> bool {
+        (0..self.shards.len()).all(|shard| self.lock(shard).is_empty())
+    }
+
+    /// Checks if the key refers to a live element
+    pub fn contains_key(&self, key: SIVKey) -> bool {
+        let (shard, key) = self.route(key);
+        self.lock(shard).contains_key(key)
+    }
+
+    /// Adds an element to the next shard and returns its key. Panics if the shard's id no longer fits in a
+    /// combined key, i.e. once a shard holds more than `usize::MAX / shard_count` ids
+    pub fn add(&self, el: T) -> SIVKey {
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let mut inner = self.lock(shard);
+        let key = inner.add(el);
+        let Some(id) = key.id.checked_mul(self.shards.len()).and_then(|id| id.checked_add(shard)) else {
+            inner.remove(key);
+            drop(inner);
+            panic!("ShardedStableIndexVec shard {shard} ran out of key space");
+        };
+        SIVKey::new(id, key.generation)
+    }
+
+    /// Removes an element from the container by its key
+    pub fn remove(&self, key: SIVKey) -> Option<T> {
+        let (shard, key) = self.route(key);
+        self.lock(shard).remove(key)
+    }
+}
+
+impl<T: Clone> ShardedStableIndexVec<T> {
+    /// Gets a clone of an element by its key
+    pub fn get(&self, key: SIVKey) -> Option<T> {
+        let (shard, key) = self.route(key);
+        self.lock(shard).get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_share_one_container() {
+        let container = SyncStableIndexVec::new();
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let container = &container;
+                scope.spawn(move || {
+                    for i in 0..500 {
+                        let key = container.add(thread * 1000 + i);
+                        assert_eq!(container.get(key), Some(thread * 1000 + i));
+                        if i % 2 == 0 {
+                            assert_eq!(container.remove(key), Some(thread * 1000 + i));
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(container.len(), 8 * 250);
+        let container = container.into_inner();
+        assert!(container.validate().is_ok());
+        assert!(container.values().all(|el| el % 2 == 1));
+    }
+
+    #[test]
+    fn with_is_atomic() {
+        let mut inner = StableIndexVec::new();
+        let key = inner.add(0);
+        let container = SyncStableIndexVec::from(inner);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        container.with(|inner| *inner.get_mut(key).unwrap() += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(container.get(key), Some(8000));
+    }
+
+    #[test]
+    fn survives_a_panic_under_the_lock() {
+        let container = SyncStableIndexVec::new();
+        let key = container.add(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            container.with(|_| panic!("boom"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(container.get(key), Some(1));
+    }
+
+    #[test]
+    fn readers_see_consistent_state_during_writes() {
+        let container = SharedStableIndexVec::new();
+        let pinned = container.add(-1);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    for _ in 0..2000 {
+                        assert_eq!(container.get_cloned(pinned), Some(-1));
+                        let guard = container.read();
+                        assert_eq!(guard.iter().count(), guard.len());
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for i in 0..200 {
+                    let key = container.add(i);
+                    if i % 4 != 0 {
+                        container.remove(key);
+                    }
+                }
+            });
+        });
+
+        let container = container.into_inner();
+        assert_eq!(container.len(), 51);
+        assert!(container.validate().is_ok());
+    }
+
+    #[test]
+    fn sharded_keys_route_to_their_shard() {
+        let container = ShardedStableIndexVec::with_shards(3);
+        let keys: Vec<_> = (0..7).map(|i| container.add(i)).collect();
+        assert_eq!(keys.iter().map(|key| key.id % 3).collect::<Vec<_>>(), [0, 1, 2, 0, 1, 2, 0]);
+
+        assert_eq!(container.remove(keys[4]), Some(4));
+        assert_eq!(container.get(keys[4]), None);
+        let reused = container.add(7);
+        assert_ne!(reused, keys[4]);
+        for (i, &key) in keys.iter().enumerate().filter(|&(i, _)| i != 4) {
+            assert_eq!(container.get(key), Some(i));
+        }
+        assert_eq!(container.len(), 7);
+    }
+
+    #[test]
+    fn sharded_adds_under_contention() {
+        let container = ShardedStableIndexVec::with_shards(4);
+        let keys: Vec<Vec<_>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|thread| {
+                    let container = &container;
+                    scope.spawn(move || (0..1000).map(|i| (container.add(thread * 1000 + i), thread * 1000 + i)).collect())
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let mut seen = std::collections::HashSet::new();
+        for &(key, el) in keys.iter().flatten() {
+            assert!(seen.insert(key));
+            assert_eq!(container.get(key), Some(el));
+        }
+        assert_eq!(container.len(), 8000);
+    }
+}
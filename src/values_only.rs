@@ -0,0 +1,64 @@
+//! Serde adapter that stores only a container's values, for use with `#[serde(with = "fast_container::values_only")]`.
+//!
+//! The output is a flat array of the live values in data order, with no ids or generations, so it is much smaller
+//! than the full form. Deserializing adds the values to a fresh container under new keys, so any keys stored
+//! elsewhere stop matching. Only use this when saved handles won't be reused.
+
+use allocator_api2::alloc::Allocator;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::{IndexInt, StableIndexVec};
+
+/// Serializes the container's live values as a flat sequence
+pub fn serialize<T, I, A, S>(container: &StableIndexVec<T, I, A>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: serde::Serialize,
+    I: IndexInt,
+    A: Allocator,
+    S: Serializer,
+{
+    serializer.collect_seq(container.values_slice())
+}
+
+/// Deserializes a flat sequence of values into a fresh container, giving each one a new key
+pub fn deserialize<'de, T, I, A, D>(deserializer: D) -> Result<StableIndexVec<T, I, A>, D::Error>
+where
+    T: Deserialize<'de>,
+    I: IndexInt,
+    A: Allocator + Clone + Default,
+    D: Deserializer<'de>,
+{
+    let values = alloc::vec::Vec::<T>::deserialize(deserializer)?;
+    let mut container = StableIndexVec::default();
+    container.add_many(values);
+    Ok(container)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::StableIndexVec;
+
+    #[derive(Serialize, Deserialize)]
+    struct Saved {
+        #[serde(with = "crate::values_only")]
+        items: StableIndexVec<u32>,
+    }
+
+    #[test]
+    fn values_only_round_trips_values() {
+        let mut items = StableIndexVec::new();
+        let keys = items.add_many([1, 2, 3, 4]);
+        items.remove(keys[0]);
+        items.remove(keys[2]);
+
+        let json = serde_json::to_string(&Saved { items }).unwrap();
+        assert_eq!(json, r#"{"items":[4,2]}"#);
+
+        let loaded: Saved = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.items.values_slice(), [4, 2]);
+        assert_eq!(loaded.items.slot_count(), 2);
+        assert!(loaded.items.validate().is_ok());
+    }
+}